@@ -10,10 +10,22 @@ impl Ast {
         Ast::Node {
             rule: Rule::Empty,
             nodes: vec![],
+            loc: None,
         }
     }
     pub fn node(rule: Rule, nodes: Vec<Ast>) -> Ast {
-        Ast::Node { rule, nodes }
+        Ast::Node {
+            rule,
+            nodes,
+            loc: None,
+        }
+    }
+    pub fn node_at(rule: Rule, nodes: Vec<Ast>, loc: Loc) -> Ast {
+        Ast::Node {
+            rule,
+            nodes,
+            loc: Some(loc),
+        }
     }
 
     // ------------------------------------------------------------------
@@ -24,6 +36,21 @@ impl Ast {
         }
     }
 
+    pub fn set_loc(&mut self, new_loc: Loc) {
+        match self {
+            Ast::Leaf(..) => panic!("this method may not be called on leaf"),
+            Ast::Node { loc, .. } => *loc = Some(new_loc),
+        }
+    }
+
+    /// where in the source this node came from.
+    pub fn loc(&self) -> Option<Loc> {
+        match &self {
+            Ast::Leaf(token) => Some(token.loc()),
+            Ast::Node { loc, .. } => loc.clone(),
+        }
+    }
+
     pub fn name(&self) -> String {
         match &self {
             Ast::Leaf(..) => "Token".to_owned(),
@@ -36,6 +63,8 @@ impl Ast {
             Ast::Leaf(leaf) => match leaf.tok {
                 Tok::Float(..) => true,
                 Tok::Int(..) => true,
+                Tok::Str(..) => true,
+                Tok::Char(..) => true,
                 _ => false,
             },
             Ast::Node { rule, .. } => {
@@ -53,12 +82,17 @@ impl Ast {
     pub fn to_obj(&self) -> Obj {
         match &self {
             Ast::Leaf(leaf) => leaf.to_obj(),
-            Ast::Node { rule: _, nodes } => {
+            Ast::Node { rule, nodes, loc } => {
                 let mut objs = vec![];
                 for node in nodes {
                     objs.push(node.to_obj())
                 }
-                Obj::list_from_vec(objs, None)
+                if *rule == Rule::DottedList {
+                    let tail = objs.pop().unwrap();
+                    Obj::dotted_list_from_vec(objs, tail, loc.clone())
+                } else {
+                    Obj::list_from_vec(objs, loc.clone())
+                }
             }
         }
     }
@@ -78,7 +112,7 @@ impl Ast {
             &Ast::Leaf(tok) => {
                 tok.pretty();
             }
-            &Ast::Node { rule, nodes } => {
+            &Ast::Node { rule, nodes, .. } => {
                 match rule {
                     Rule::List => {
                         print!("(");
@@ -92,6 +126,20 @@ impl Ast {
                         }
                         print!(")");
                     }
+                    Rule::DottedList => {
+                        print!("(");
+                        let limit = nodes.len();
+                        for (i, node) in nodes.iter().enumerate() {
+                            if i == limit - 1 {
+                                print!(". ");
+                            }
+                            node.pretty();
+                            if i < limit - 1 {
+                                print!(" ");
+                            }
+                        }
+                        print!(")");
+                    }
                     Rule::Empty => {}
                     Rule::EmptyList => {
                         print!("()");
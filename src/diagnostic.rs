@@ -0,0 +1,187 @@
+use crate::types::*;
+use std::fmt;
+
+// Diagnostics render an error against the source it came from:
+//
+// error: car was not passed a cons cell
+//  --> fact.scm:3:5
+//   |
+// 3 |     (car 5)
+//   |     ^^^^^^^
+//   = note: ...
+//   = help: ...
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub loc: Option<Loc>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.to_owned(),
+            loc: None,
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    pub fn error(message: &str) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: &str) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    pub fn at(mut self, loc: Option<Loc>) -> Diagnostic {
+        self.loc = loc;
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_owned());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Diagnostic {
+        self.help = Some(help.to_owned());
+        self
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        out += &format!("{}: {}\n", severity, self.message);
+
+        let snippet = self.loc.as_ref().and_then(snippet);
+        // the gutter is as wide as the line number it shows.
+        let gutter = match &snippet {
+            Some((line, _, _)) => " ".repeat(line.to_string().len()),
+            None => " ".to_owned(),
+        };
+
+        if let Some(loc) = &self.loc {
+            out += &format!("{}--> {}\n", gutter, loc);
+        }
+        if let Some((line, text, underline)) = &snippet {
+            out += &format!("{} |\n", gutter);
+            out += &format!("{} | {}\n", line, text);
+            out += &format!("{} | {}\n", gutter, underline);
+        }
+        for note in self.notes.iter() {
+            out += &format!("{} = note: {}\n", gutter, note);
+        }
+        if let Some(help) = &self.help {
+            out += &format!("{} = help: {}\n", gutter, help);
+        }
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+// the line number, text of the first line of loc, and a ^^^ line
+// under the part of it loc covers. locs spanning several lines are
+// underlined to the end of the first.
+fn snippet(loc: &Loc) -> Option<(usize, String, String)> {
+    let src = loc.file.source();
+    let (line, _) = src.line_col(loc.start);
+    let text = src.line(line)?;
+    let line_start = src.line_start(line)?;
+
+    let start = (loc.start - line_start).min(text.len());
+    let end = (loc.end.saturating_sub(line_start))
+        .min(text.len())
+        .max(start);
+
+    // keep tabs so the carets line up with the text above them.
+    let mut underline: String = text[..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = if end > start {
+        text[start..end].chars().count()
+    } else {
+        1
+    };
+    underline += &"^".repeat(width);
+    Some((line, text.to_owned(), underline))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::FileId;
+
+    #[test]
+    fn render_with_snippet() {
+        let file = FileId::new("test.scm", "(define x 1)\n(car x)\n");
+        let loc = Loc::new(file, 13, 20);
+        let diagnostic = Diagnostic::error("car was not passed a cons cell")
+            .at(Some(loc))
+            .with_note("x is 1")
+            .with_help("pass a list to car");
+        let expected = "\
+error: car was not passed a cons cell
+ --> test.scm:2:1
+  |
+2 | (car x)
+  | ^^^^^^^
+  = note: x is 1
+  = help: pass a list to car
+";
+        assert_eq!(diagnostic.render(), expected);
+    }
+
+    #[test]
+    fn render_multiline_and_empty_locs() {
+        let file = FileId::new("test.scm", "(a\n\t(b c)\n d)");
+        let inner = Loc::new(file.clone(), 5, 6);
+        assert!(Diagnostic::error("e")
+            .at(Some(inner))
+            .render()
+            .ends_with("2 | \t(b c)\n  | \t ^\n"));
+
+        // underlined to the end of the first line.
+        let outer = Loc::new(file.clone(), 0, 14);
+        assert!(Diagnostic::error("e")
+            .at(Some(outer))
+            .render()
+            .ends_with("1 | (a\n  | ^^\n"));
+
+        // an empty loc at the end of input still gets a caret.
+        let eof = Loc::new(file, 14, 14);
+        assert!(Diagnostic::error("e")
+            .at(Some(eof))
+            .render()
+            .ends_with("3 |  d)\n  |    ^\n"));
+    }
+
+    #[test]
+    fn render_without_loc() {
+        let diagnostic = Diagnostic::warning("no location").with_help("none");
+        assert_eq!(
+            diagnostic.render(),
+            "warning: no location\n  = help: none\n"
+        );
+    }
+}
@@ -1,21 +1,37 @@
+use crate::eval;
+use crate::heap;
+use crate::syntax;
 use crate::types::*;
 
 // primitive procedures
-// these primitive procedures accept a list of arguments.
+// these primitive procedures accept a list of arguments. how many
+// is checked against the arity they are added with before they run,
+// those with optional arguments check the rest themselves.
 
-fn car(xs: Obj) -> EvalResult<Obj> {
-    if xs.list_length()? > 1 {
-        Err(format!("car only takes one argument, got: {:?}", xs))
+fn expect_args(name: &str, xs: &Obj, n: usize) -> EvalResult<()> {
+    let got = xs.list_length()?;
+    if got != n {
+        Err(LazarusError::arity(name, &n.to_string(), got))
     } else {
-        xs.car()?.car()
+        Ok(())
     }
 }
+
+// (error msg irritant ...)
+fn error(xs: Obj) -> EvalResult<Obj> {
+    let msg = xs.car()?;
+    let msg = match &*msg.val.borrow() {
+        ObjVal::Str(s) => s.clone(),
+        _ => format!("{:?}", msg),
+    };
+    Err(LazarusError::user(&msg, xs.cdr()?))
+}
+
+fn car(xs: Obj) -> EvalResult<Obj> {
+    xs.car()?.car()
+}
 pub fn cdr(xs: Obj) -> EvalResult<Obj> {
-    if xs.list_length()? > 1 {
-        Err(format!("cdr only takes one argument, got: {:?}", xs))
-    } else {
-        xs.car()?.cdr()
-    }
+    xs.car()?.cdr()
 }
 
 fn list(xs: Obj) -> EvalResult<Obj> {
@@ -80,15 +96,68 @@ fn dec(xs: Obj) -> EvalResult<Obj> {
 }
 
 fn cons(xs: Obj) -> EvalResult<Obj> {
-    if xs.list_length()? != 2 {
-        Err(format!("cons must take 2 args, got: {:?}", xs))
-    } else {
-        Ok(Obj::cons(xs.car()?, xs.cadr()?))
-    }
+    Ok(Obj::cons(xs.car()?, xs.cadr()?))
 }
 
 fn eq(xs: Obj) -> EvalResult<Obj> {
-    Ok(Obj::new_bool(xs.car()? == xs.cadr()?, None))
+    Ok(Obj::new_bool(xs.car()?.is_eq(&xs.cadr()?), None))
+}
+
+fn eqv(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.is_eqv(&xs.cadr()?), None))
+}
+
+fn equal(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.is_equal(&xs.cadr()?), None))
+}
+
+fn is_procedure(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.is_procedure(), None))
+}
+
+// (heap-stats) counts what the program keeps alive, (heap-stats obj)
+// what obj does.
+fn heap_stats(xs: Obj) -> EvalResult<Obj> {
+    if xs.is_null()? {
+        return Ok(heap::heap_stats(&global_environment()?).to_obj());
+    }
+    expect_args("heap-stats", &xs, 1)?;
+    Ok(heap::heap_stats_of(&xs.car()?).to_obj())
+}
+
+// (heap-cycles) or (heap-cycles obj), the names of the closures in
+// env <-> closure cycles.
+fn heap_cycles(xs: Obj) -> EvalResult<Obj> {
+    let names = if xs.is_null()? {
+        heap::closure_cycles(&global_environment()?)
+    } else {
+        expect_args("heap-cycles", &xs, 1)?;
+        heap::closure_cycles_of(&xs.car()?)
+    };
+    let names = names
+        .into_iter()
+        .map(|name| Obj::new_symb(&name, None))
+        .collect();
+    Ok(Obj::list_from_vec(names, None))
+}
+
+fn global_environment() -> EvalResult<Env> {
+    eval::global_environment().ok_or_else(|| LazarusError::eval("no program is being evaluated"))
+}
+
+// (gensym) or (gensym prefix), a symbol no other symbol is eq? to.
+fn gensym(xs: Obj) -> EvalResult<Obj> {
+    if xs.is_null()? {
+        return Ok(Obj::from_symb(Symb::gensym("g"), None));
+    }
+    expect_args("gensym", &xs, 1)?;
+    let prefix = xs.car()?;
+    let symb = match &*prefix.val.borrow() {
+        ObjVal::Str(s) => Symb::gensym(s),
+        ObjVal::Symbol(s) => Symb::gensym(s.name()),
+        _ => return Err(LazarusError::wrong_type("string or symbol", &prefix)),
+    };
+    Ok(Obj::from_symb(symb, None))
 }
 
 fn lt(xs: Obj) -> EvalResult<Obj> {
@@ -105,6 +174,214 @@ fn gt(xs: Obj) -> EvalResult<Obj> {
     Ok(Obj::new_bool(lhs > rhs, None))
 }
 
+// strings ------------------------------------------------------------------
+
+fn string_index(s: &str, k: i64) -> EvalResult<usize> {
+    let len = s.chars().count();
+    if k < 0 || k as usize > len {
+        let msg = format!("string index out of range: {} for {:?}", k, s);
+        Err(LazarusError::eval(&msg))
+    } else {
+        Ok(k as usize)
+    }
+}
+
+fn string_length(xs: Obj) -> EvalResult<Obj> {
+    let s = xs.car()?.str_val()?;
+    Ok(Obj::new_int(s.chars().count() as i64, xs.loc.clone()))
+}
+
+fn string_ref(xs: Obj) -> EvalResult<Obj> {
+    let s = xs.car()?.str_val()?;
+    let k = xs.cadr()?.int_val()?;
+    match s.chars().nth(string_index(&s, k)?) {
+        Some(c) => Ok(Obj::new_char(c, xs.loc.clone())),
+        None => {
+            let msg = format!("string index out of range: {} for {:?}", k, s);
+            Err(LazarusError::eval(&msg))
+        }
+    }
+}
+
+fn substring(xs: Obj) -> EvalResult<Obj> {
+    let n = xs.list_length()?;
+    if n != 2 && n != 3 {
+        return Err(LazarusError::arity("substring", "2 or 3", n));
+    }
+    let s = xs.car()?.str_val()?;
+    let start = string_index(&s, xs.cadr()?.int_val()?)?;
+    let end = if n == 3 {
+        string_index(&s, xs.caddr()?.int_val()?)?
+    } else {
+        s.chars().count()
+    };
+    if start > end {
+        let msg = format!("substring start {} is after end {}", start, end);
+        return Err(LazarusError::eval(&msg));
+    }
+    let sub: String = s.chars().skip(start).take(end - start).collect();
+    Ok(Obj::new_str(sub, xs.loc.clone()))
+}
+
+fn string_append(xs: Obj) -> EvalResult<Obj> {
+    let mut result = String::new();
+    for x in xs.list_to_vec()? {
+        result.push_str(&x.str_val()?);
+    }
+    Ok(Obj::new_str(result, xs.loc.clone()))
+}
+
+fn string_eq(xs: Obj) -> EvalResult<Obj> {
+    let strs = xs.list_to_vec()?;
+    let first = strs[0].str_val()?;
+    for x in strs.iter().skip(1) {
+        if x.str_val()? != first {
+            return Ok(Obj::new_bool(false, None));
+        }
+    }
+    Ok(Obj::new_bool(true, None))
+}
+
+fn string_to_symbol(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_symb(&xs.car()?.str_val()?, xs.loc.clone()))
+}
+
+fn symbol_to_string(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_str(xs.car()?.symbol_name()?, xs.loc.clone()))
+}
+
+fn string_to_list(xs: Obj) -> EvalResult<Obj> {
+    // (string->list s) is (string->list s 0)
+    let args = if xs.list_length()? == 1 {
+        Obj::list_from_vec(vec![xs.car()?, Obj::new_int(0, None)], None)
+    } else {
+        xs.clone()
+    };
+    let sub = substring(args)?;
+    let chars = sub
+        .str_val()?
+        .chars()
+        .map(|c| Obj::new_char(c, xs.loc.clone()))
+        .collect();
+    Ok(Obj::list_from_vec(chars, xs.loc.clone()))
+}
+
+// chars ------------------------------------------------------------------
+
+fn is_char(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.is_char(), xs.loc.clone()))
+}
+
+fn char_to_integer(xs: Obj) -> EvalResult<Obj> {
+    let c = xs.car()?.char_val()?;
+    Ok(Obj::new_int(c as i64, xs.loc.clone()))
+}
+
+fn integer_to_char(xs: Obj) -> EvalResult<Obj> {
+    let n = xs.car()?.int_val()?;
+    let c = if n < 0 || n > u32::MAX as i64 {
+        None
+    } else {
+        std::char::from_u32(n as u32)
+    };
+    match c {
+        Some(c) => Ok(Obj::new_char(c, xs.loc.clone())),
+        None => Err(LazarusError::wrong_type("unicode scalar value", &xs.car()?)),
+    }
+}
+
+// to_uppercase may expand to several chars (ß -> SS), in which case
+// the char is left alone.
+fn single_char<I: Iterator<Item = char>>(c: char, mut cs: I) -> char {
+    match (cs.next(), cs.next()) {
+        (Some(x), None) => x,
+        _ => c,
+    }
+}
+
+fn char_upcase(xs: Obj) -> EvalResult<Obj> {
+    let c = xs.car()?.char_val()?;
+    Ok(Obj::new_char(
+        single_char(c, c.to_uppercase()),
+        xs.loc.clone(),
+    ))
+}
+
+fn char_downcase(xs: Obj) -> EvalResult<Obj> {
+    let c = xs.car()?.char_val()?;
+    Ok(Obj::new_char(
+        single_char(c, c.to_lowercase()),
+        xs.loc.clone(),
+    ))
+}
+
+fn char_foldcase(c: char) -> char {
+    single_char(c, c.to_lowercase())
+}
+
+fn char_is_alphabetic(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.char_val()?.is_alphabetic(), None))
+}
+
+fn char_is_numeric(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.char_val()?.is_numeric(), None))
+}
+
+fn char_is_whitespace(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.char_val()?.is_whitespace(), None))
+}
+
+fn char_is_upper_case(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.char_val()?.is_uppercase(), None))
+}
+
+fn char_is_lower_case(xs: Obj) -> EvalResult<Obj> {
+    Ok(Obj::new_bool(xs.car()?.char_val()?.is_lowercase(), None))
+}
+
+// compare every adjacent pair of chars in xs with cmp, optionally
+// case folding them first.
+fn char_compare(xs: Obj, cmp: fn(&char, &char) -> bool, fold: bool) -> EvalResult<Obj> {
+    let mut chars = vec![];
+    for x in xs.list_to_vec()? {
+        let c = x.char_val()?;
+        chars.push(if fold { char_foldcase(c) } else { c });
+    }
+    let result = chars.windows(2).all(|w| cmp(&w[0], &w[1]));
+    Ok(Obj::new_bool(result, None))
+}
+
+fn char_eq(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::eq, false)
+}
+fn char_lt(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::lt, false)
+}
+fn char_gt(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::gt, false)
+}
+fn char_le(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::le, false)
+}
+fn char_ge(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::ge, false)
+}
+fn char_ci_eq(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::eq, true)
+}
+fn char_ci_lt(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::lt, true)
+}
+fn char_ci_gt(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::gt, true)
+}
+fn char_ci_le(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::le, true)
+}
+fn char_ci_ge(xs: Obj) -> EvalResult<Obj> {
+    char_compare(xs, char::ge, true)
+}
+
 // ------------------------------------------------------------------
 impl Env {
     pub fn new(id: usize) -> Env {
@@ -118,7 +395,7 @@ impl Env {
     pub fn the_global_environment() -> Env {
         let mut env = Env::new(0);
         let mut add_obj = |s: &str, obj| {
-            env.define_variable(&Symb::new_unknown(s), obj);
+            env.define_variable(&Symb::intern(s), obj);
         };
 
         add_obj("true", Obj::new_bool(true, None));
@@ -126,39 +403,89 @@ impl Env {
         add_obj("#t", Obj::new_bool(true, None));
         add_obj("#f", Obj::new_bool(false, None));
 
-        env.add_primitive_func("car", car);
-        env.add_primitive_func("cdr", cdr);
-        env.add_primitive_func("list", list);
-        env.add_primitive_func("null?", is_null);
-        env.add_primitive_func("mul", mul);
-        env.add_primitive_func("*", mul);
-        env.add_primitive_func("+", add);
-        env.add_primitive_func("-", sub);
-        env.add_primitive_func(">", gt);
-        env.add_primitive_func("<", lt);
-        env.add_primitive_func("cons", cons);
-        env.add_primitive_func("eq?", eq);
-        env.add_primitive_func("dec", dec);
+        env.add_primitive_func("car", car, Arity::exactly(1));
+        env.add_primitive_func("cdr", cdr, Arity::exactly(1));
+        env.add_primitive_func("list", list, Arity::at_least(0));
+        env.add_primitive_func("null?", is_null, Arity::exactly(1));
+        env.add_primitive_func("mul", mul, Arity::at_least(0));
+        env.add_primitive_func("*", mul, Arity::at_least(0));
+        env.add_primitive_func("+", add, Arity::at_least(0));
+        env.add_primitive_func("-", sub, Arity::at_least(0));
+        env.add_primitive_func(">", gt, Arity::exactly(2));
+        env.add_primitive_func("<", lt, Arity::exactly(2));
+        env.add_primitive_func("cons", cons, Arity::exactly(2));
+        env.add_primitive_func("eq?", eq, Arity::exactly(2));
+        env.add_primitive_func("eqv?", eqv, Arity::exactly(2));
+        env.add_primitive_func("equal?", equal, Arity::exactly(2));
+        env.add_primitive_func("procedure?", is_procedure, Arity::exactly(1));
+        env.add_primitive_func("gensym", gensym, Arity::at_least(0));
+        env.add_primitive_func("heap-stats", heap_stats, Arity::at_least(0));
+        env.add_primitive_func("heap-cycles", heap_cycles, Arity::at_least(0));
+        env.add_primitive_func(
+            "er-macro-transformer",
+            syntax::er_macro_transformer,
+            Arity::exactly(1),
+        );
+        env.add_primitive_func("dec", dec, Arity::exactly(1));
+        env.add_primitive_func("error", error, Arity::at_least(1));
+
+        env.add_primitive_func("string-length", string_length, Arity::exactly(1));
+        env.add_primitive_func("string-ref", string_ref, Arity::exactly(2));
+        env.add_primitive_func("substring", substring, Arity::at_least(2));
+        env.add_primitive_func("string-append", string_append, Arity::at_least(0));
+        env.add_primitive_func("string=?", string_eq, Arity::at_least(1));
+        env.add_primitive_func("string->symbol", string_to_symbol, Arity::exactly(1));
+        env.add_primitive_func("symbol->string", symbol_to_string, Arity::exactly(1));
+        env.add_primitive_func("string->list", string_to_list, Arity::at_least(1));
+
+        env.add_primitive_func("char?", is_char, Arity::exactly(1));
+        env.add_primitive_func("char->integer", char_to_integer, Arity::exactly(1));
+        env.add_primitive_func("integer->char", integer_to_char, Arity::exactly(1));
+        env.add_primitive_func("char-upcase", char_upcase, Arity::exactly(1));
+        env.add_primitive_func("char-downcase", char_downcase, Arity::exactly(1));
+        env.add_primitive_func("char-alphabetic?", char_is_alphabetic, Arity::exactly(1));
+        env.add_primitive_func("char-numeric?", char_is_numeric, Arity::exactly(1));
+        env.add_primitive_func("char-whitespace?", char_is_whitespace, Arity::exactly(1));
+        env.add_primitive_func("char-upper-case?", char_is_upper_case, Arity::exactly(1));
+        env.add_primitive_func("char-lower-case?", char_is_lower_case, Arity::exactly(1));
+        env.add_primitive_func("char=?", char_eq, Arity::at_least(1));
+        env.add_primitive_func("char<?", char_lt, Arity::at_least(1));
+        env.add_primitive_func("char>?", char_gt, Arity::at_least(1));
+        env.add_primitive_func("char<=?", char_le, Arity::at_least(1));
+        env.add_primitive_func("char>=?", char_ge, Arity::at_least(1));
+        env.add_primitive_func("char-ci=?", char_ci_eq, Arity::at_least(1));
+        env.add_primitive_func("char-ci<?", char_ci_lt, Arity::at_least(1));
+        env.add_primitive_func("char-ci>?", char_ci_gt, Arity::at_least(1));
+        env.add_primitive_func("char-ci<=?", char_ci_le, Arity::at_least(1));
+        env.add_primitive_func("char-ci>=?", char_ci_ge, Arity::at_least(1));
         env
     }
 
-    pub fn add_primitive_func(&mut self, funcname: &str, func: fn(Obj) -> EvalResult<Obj>) {
-        let proc = Obj::list_from_vec(
-            vec![
-                Obj::new_symb("primitive".to_owned(), None),
-                Obj::new_primitive_func(func, None),
-            ],
-            None,
-        );
-        self.define_variable(&Symb::new_unknown(funcname), proc);
+    pub fn add_primitive_func(
+        &mut self,
+        funcname: &str,
+        func: fn(Obj) -> EvalResult<Obj>,
+        arity: Arity,
+    ) {
+        let proc = Obj::new_primitive(funcname, func, arity);
+        self.define_variable(&Symb::intern(funcname), proc);
     }
 
     pub fn is_global(&self) -> bool {
         self.enclosing.is_none()
     }
 
+    /// the outermost environment self is enclosed in, or self.
+    pub fn global(&self) -> Env {
+        let mut env = self;
+        while let Some(enclosing) = env.enclosing.as_deref() {
+            env = enclosing;
+        }
+        env.clone()
+    }
+
     pub fn define_variable(&mut self, var: &Symb, obj: Obj) {
-        self.frame.borrow_mut().insert(var.clone(), obj);
+        self.frame.borrow_mut().insert(*var, obj);
     }
 
     // Returns the value that is bound to the symbol ⟨var⟩ in the
@@ -169,7 +496,7 @@ impl Env {
             Some(value) => Ok(value),
             None => {
                 if self.is_global() {
-                    Err(format!("undefined variable: {}", var.name))
+                    Err(LazarusError::unbound_variable(var.name()))
                 } else {
                     self.enclosing.as_ref().unwrap().lookup_variable_value(var)
                 }
@@ -177,6 +504,86 @@ impl Env {
         }
     }
 
+    // the value bound to var in env or an enclosing one.
+    fn lookup(&self, var: &Symb) -> Option<Obj> {
+        let mut next = Some(self);
+        while let Some(env) = next {
+            if let Some(value) = env.frame.borrow().get(var) {
+                return Some(value);
+            }
+            next = env.enclosing.as_deref();
+        }
+        None
+    }
+
+    /// the value of an identifier. an alias is looked up by its fresh
+    /// symbol first, in case the expansion bound it, and when that is
+    /// unbound by its base in the environment the macro was defined in.
+    pub fn lookup_identifier(&self, id: &Obj) -> EvalResult<Obj> {
+        match &*id.val.borrow() {
+            ObjVal::Alias(alias) => match self.lookup(&alias.symb) {
+                Some(value) => Ok(value),
+                None => alias.env.lookup_identifier(&alias.base),
+            },
+            _ => self.lookup_variable_value(&id.to_symb()?),
+        }
+    }
+
+    /// the binding an identifier refers to, the frame that binds it and
+    /// the symbol it is bound by, or no frame and its name when it is
+    /// free. two identifiers are the same one, free-identifier=?, when
+    /// they refer to the same binding.
+    pub fn binding_of(&self, id: &Obj) -> EvalResult<(Option<usize>, Symb)> {
+        match &*id.val.borrow() {
+            ObjVal::Alias(alias) => match self.frame_binding(&alias.symb) {
+                Some(frame) => Ok((Some(frame), alias.symb)),
+                None => alias.env.binding_of(&alias.base),
+            },
+            _ => {
+                let symb = id.to_symb()?;
+                Ok((self.frame_binding(&symb), symb))
+            }
+        }
+    }
+
+    // the frame of the env var is bound in, by address.
+    fn frame_binding(&self, var: &Symb) -> Option<usize> {
+        let mut next = Some(self);
+        while let Some(env) = next {
+            if env.frame.borrow().get(var).is_some() {
+                return Some(&*env.frame as *const _ as usize);
+            }
+            next = env.enclosing.as_deref();
+        }
+        None
+    }
+
+    /// the macro an identifier is bound to, None when it is unbound or
+    /// bound to anything else.
+    pub fn lookup_macro(&self, id: &Obj) -> Option<Macro> {
+        let value = match &*id.val.borrow() {
+            ObjVal::Alias(alias) => match self.lookup(&alias.symb) {
+                Some(value) => value,
+                None => return alias.env.lookup_macro(&alias.base),
+            },
+            ObjVal::Symbol(symb) => self.lookup(symb)?,
+            _ => return None,
+        };
+        value.to_macro().ok()
+    }
+
+    /// set! of an identifier, an alias is found the same way as by
+    /// lookup_identifier.
+    pub fn set_identifier(&mut self, id: &Obj, obj: Obj) -> EvalResult<()> {
+        match &*id.val.borrow() {
+            ObjVal::Alias(alias) => match self.lookup(&alias.symb) {
+                Some(_) => self.set_variable_value(&alias.symb, obj),
+                None => alias.env.clone().set_identifier(&alias.base, obj),
+            },
+            _ => self.set_variable_value(&id.to_symb()?, obj),
+        }
+    }
+
     pub fn set_variable_value(&mut self, var: &Symb, obj: Obj) -> EvalResult<()> {
         let val = self.frame.borrow().get(var);
         match val {
@@ -186,7 +593,7 @@ impl Env {
             }
             None => {
                 if self.is_global() {
-                    Err(format!("Unbound varaible: SET! {:?}", var))
+                    Err(LazarusError::unbound_variable(var.name()))
                 } else {
                     self.enclosing
                         .as_mut()
@@ -207,7 +614,7 @@ mod tests {
     fn env_define_check() {
         let env = Env::new(0);
         let mut inner = Env::new(1);
-        let sym = Symb::new("x", "env.rs".to_owned(), 42);
+        let sym = Symb::intern("x");
         let obj1 = Obj::new_int(12, None);
         let _obj3 = Obj::new_int(345345, None);
         let obj2 = obj1.clone();
@@ -224,7 +631,7 @@ mod tests {
         let mut outer = Env::new(0);
         let mut mid = Env::new(1);
         let mut inner = Env::new(2);
-        let sym = Symb::new("x", "env.rs".to_owned(), 42);
+        let sym = Symb::intern("x");
         let obj1 = Obj::new_int(12, None);
         let _obj3 = Obj::new_int(345345, None);
         let obj2 = obj1.clone();
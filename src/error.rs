@@ -0,0 +1,307 @@
+use crate::diagnostic::Diagnostic;
+use crate::parser::ParseError;
+use crate::types::*;
+use std::error::Error;
+use std::fmt;
+
+const BACKTRACE_FRAMES: usize = 20;
+
+/// one procedure call that was active when an error was raised.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub name: String,
+    /// where the call was made from.
+    pub loc: Option<Loc>,
+    pub args: Vec<Obj>,
+}
+
+impl fmt::Display for CallFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.loc {
+            Some(loc) => write!(f, "{} at {}", self.name, loc),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// what every error carries besides its kind: where it happened,
+/// the object at fault and the scheme call stack at the time.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorContext {
+    pub loc: Option<Loc>,
+    pub obj: Option<Obj>,
+    /// outermost call first.
+    pub stack: Vec<CallFrame>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LazarusError {
+    UnboundVariable {
+        name: String,
+        ctx: Box<ErrorContext>,
+    },
+    /// ctx.obj is the value that had the wrong type.
+    WrongType {
+        expected: String,
+        ctx: Box<ErrorContext>,
+    },
+    Arity {
+        procedure: String,
+        expected: String,
+        got: usize,
+        ctx: Box<ErrorContext>,
+    },
+    Parse {
+        msg: String,
+        ctx: Box<ErrorContext>,
+    },
+    /// raised from scheme with (error msg irritant ...), ctx.obj is
+    /// the list of irritants.
+    User {
+        msg: String,
+        ctx: Box<ErrorContext>,
+    },
+    /// nested evaluation went deeper than the configured maximum,
+    /// see eval::set_max_depth.
+    DepthExceeded {
+        limit: usize,
+        ctx: Box<ErrorContext>,
+    },
+    /// the fuel given with eval::set_fuel ran out.
+    OutOfFuel {
+        ctx: Box<ErrorContext>,
+    },
+    /// the flag given with eval::set_interrupt was set.
+    Interrupted {
+        ctx: Box<ErrorContext>,
+    },
+    /// malformed special forms and anything else the evaluator can't
+    /// make sense of.
+    Eval {
+        msg: String,
+        ctx: Box<ErrorContext>,
+    },
+}
+
+impl LazarusError {
+    // constructors
+    pub fn unbound_variable(name: &str) -> LazarusError {
+        LazarusError::UnboundVariable {
+            name: name.to_owned(),
+            ctx: Box::default(),
+        }
+    }
+
+    pub fn wrong_type(expected: &str, obj: &Obj) -> LazarusError {
+        LazarusError::WrongType {
+            expected: expected.to_owned(),
+            ctx: Box::default(),
+        }
+        .with_obj(obj)
+    }
+
+    pub fn arity(procedure: &str, expected: &str, got: usize) -> LazarusError {
+        LazarusError::Arity {
+            procedure: procedure.to_owned(),
+            expected: expected.to_owned(),
+            got,
+            ctx: Box::default(),
+        }
+    }
+
+    pub fn parse(msg: &str, loc: Option<Loc>) -> LazarusError {
+        LazarusError::Parse {
+            msg: msg.to_owned(),
+            ctx: Box::default(),
+        }
+        .at(loc)
+    }
+
+    pub fn user(msg: &str, irritants: Obj) -> LazarusError {
+        LazarusError::User {
+            msg: msg.to_owned(),
+            ctx: Box::default(),
+        }
+        .with_obj(&irritants)
+    }
+
+    pub fn depth_exceeded(limit: usize) -> LazarusError {
+        LazarusError::DepthExceeded {
+            limit,
+            ctx: Box::default(),
+        }
+    }
+
+    pub fn out_of_fuel() -> LazarusError {
+        LazarusError::OutOfFuel {
+            ctx: Box::default(),
+        }
+    }
+
+    pub fn interrupted() -> LazarusError {
+        LazarusError::Interrupted {
+            ctx: Box::default(),
+        }
+    }
+
+    pub fn eval(msg: &str) -> LazarusError {
+        LazarusError::Eval {
+            msg: msg.to_owned(),
+            ctx: Box::default(),
+        }
+    }
+
+    // ------------------------------------------------------------------
+    pub fn ctx(&self) -> &ErrorContext {
+        match self {
+            LazarusError::UnboundVariable { ctx, .. } => ctx,
+            LazarusError::WrongType { ctx, .. } => ctx,
+            LazarusError::Arity { ctx, .. } => ctx,
+            LazarusError::Parse { ctx, .. } => ctx,
+            LazarusError::User { ctx, .. } => ctx,
+            LazarusError::DepthExceeded { ctx, .. } => ctx,
+            LazarusError::OutOfFuel { ctx } => ctx,
+            LazarusError::Interrupted { ctx } => ctx,
+            LazarusError::Eval { ctx, .. } => ctx,
+        }
+    }
+
+    pub fn ctx_mut(&mut self) -> &mut ErrorContext {
+        match self {
+            LazarusError::UnboundVariable { ctx, .. } => ctx,
+            LazarusError::WrongType { ctx, .. } => ctx,
+            LazarusError::Arity { ctx, .. } => ctx,
+            LazarusError::Parse { ctx, .. } => ctx,
+            LazarusError::User { ctx, .. } => ctx,
+            LazarusError::DepthExceeded { ctx, .. } => ctx,
+            LazarusError::OutOfFuel { ctx } => ctx,
+            LazarusError::Interrupted { ctx } => ctx,
+            LazarusError::Eval { ctx, .. } => ctx,
+        }
+    }
+
+    pub fn loc(&self) -> Option<&Loc> {
+        self.ctx().loc.as_ref()
+    }
+
+    pub fn obj(&self) -> Option<&Obj> {
+        self.ctx().obj.as_ref()
+    }
+
+    pub fn stack(&self) -> &[CallFrame] {
+        &self.ctx().stack
+    }
+
+    pub fn at(mut self, loc: Option<Loc>) -> LazarusError {
+        self.ctx_mut().loc = loc;
+        self
+    }
+
+    /// fill in the loc only if the error doesn't have one yet, so
+    /// the innermost expression with a location wins.
+    pub fn or_at(mut self, loc: &Option<Loc>) -> LazarusError {
+        if self.ctx().loc.is_none() {
+            self.ctx_mut().loc = loc.clone();
+        }
+        self
+    }
+
+    /// the object at fault. its own loc is not used, computed values
+    /// carry the loc of whatever literal they came from, so the error
+    /// is placed at the expression being evaluated instead.
+    pub fn with_obj(mut self, obj: &Obj) -> LazarusError {
+        self.ctx_mut().obj = Some(obj.clone());
+        self
+    }
+
+    pub fn with_stack(mut self, stack: Vec<CallFrame>) -> LazarusError {
+        self.ctx_mut().stack = stack;
+        self
+    }
+
+    /// the call stack innermost call first, like
+    /// "in fact-iter at fact.scm:8:11, called from factorial at ...".
+    /// only the innermost BACKTRACE_FRAMES calls are shown.
+    pub fn backtrace(&self) -> String {
+        let stack = self.stack();
+        if stack.is_empty() {
+            return String::new();
+        }
+        let frames: Vec<String> = stack
+            .iter()
+            .rev()
+            .take(BACKTRACE_FRAMES)
+            .map(|frame| frame.to_string())
+            .collect();
+        let mut backtrace = format!("in {}", frames.join(", called from "));
+        if stack.len() > BACKTRACE_FRAMES {
+            backtrace += &format!(" and {} more", stack.len() - BACKTRACE_FRAMES);
+        }
+        backtrace
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(&self.to_string()).at(self.loc().cloned());
+        if !self.stack().is_empty() {
+            diagnostic = diagnostic.with_note(&self.backtrace());
+        }
+        if let LazarusError::UnboundVariable { .. } = self {
+            diagnostic = diagnostic.with_help("define it before it is used");
+        }
+        diagnostic
+    }
+}
+
+impl fmt::Display for LazarusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LazarusError::UnboundVariable { name, .. } => write!(f, "undefined variable: {}", name),
+            LazarusError::WrongType { expected, ctx } => match &ctx.obj {
+                Some(obj) => write!(
+                    f,
+                    "wrong type: expected {}, got {}: {:?}",
+                    expected,
+                    obj.describe_type(),
+                    obj
+                ),
+                None => write!(f, "wrong type: expected {}", expected),
+            },
+            LazarusError::Arity {
+                procedure,
+                expected,
+                got,
+                ..
+            } => write!(
+                f,
+                "{}: wrong number of arguments, expected {}, got {}",
+                procedure, expected, got
+            ),
+            LazarusError::Parse { msg, .. } => write!(f, "{}", msg),
+            LazarusError::User { msg, ctx } => {
+                write!(f, "{}", msg)?;
+                if let Some(irritants) = &ctx.obj {
+                    if let Ok(irritants) = irritants.list_to_vec() {
+                        for irritant in irritants {
+                            write!(f, " {:?}", irritant)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            LazarusError::DepthExceeded { limit, .. } => {
+                write!(f, "maximum recursion depth exceeded: {}", limit)
+            }
+            LazarusError::OutOfFuel { .. } => write!(f, "out of fuel"),
+            LazarusError::Interrupted { .. } => write!(f, "evaluation interrupted"),
+            LazarusError::Eval { msg, .. } => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for LazarusError {}
+
+impl From<ParseError> for LazarusError {
+    fn from(err: ParseError) -> LazarusError {
+        LazarusError::parse(&err.msg, Some(err.loc))
+    }
+}
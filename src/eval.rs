@@ -1,82 +1,621 @@
+use crate::syntax;
 use crate::types::*;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 fn eval_assignment(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
     let var = exp.assignment_variable()?;
     let val = exp.assignment_value()?;
-    env.set_variable_value(&var, val.clone())?;
+    env.set_identifier(&var, val.clone())?;
     Ok(val.clone())
 }
 
 fn eval_definition(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
     let var = exp.definition_variable()?.to_symb()?;
-    let val = eval(exp.definition_value()?, env)?;
+    let value = exp.definition_value()?;
+    let is_lambda = value.is_lambda() || value.is_case_lambda();
+    let val = eval(value, env)?;
+    if is_lambda {
+        val.name_procedure(var);
+    }
     //println!("defining_variable: {:?}", var);
     env.define_variable(&var, val.clone());
-    Ok(Obj::new_symb("ok".to_owned(), exp.loc.clone()))
+    Ok(Obj::from_symb(Symb::OK, exp.loc.clone()))
 }
 
+// evaluates the predicate and returns the branch to evaluate, eval
+// runs it in tail position.
 fn eval_if(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
     if eval(exp.if_predicate()?, env)?.is_true() {
-        eval(exp.if_consequent()?, env)
+        exp.if_consequent()
+    } else {
+        exp.if_alternative()
+    }
+}
+
+// conditionals ----------------------------------------------------------------
+// like if, these evaluate their tests and return what is left in tail
+// position, the last expression of the chosen clause or the call of the
+// receiver of a => clause. when nothing is chosen the result is #f, as
+// for an if without an alternative.
+
+fn eval_cond(exp: Obj, env: &mut Env) -> EvalResult<Step> {
+    let mut clauses = exp.cond_clauses()?;
+    while !clauses.is_null()? {
+        let clause = clauses.car()?;
+        if clause.is_cond_else_clause()? {
+            return Ok(Step::Eval(eval_sequence(clause.cond_actions()?, env)?));
+        }
+        let test = eval(clause.cond_predicate()?, env)?;
+        if test.is_true() {
+            return eval_clause(clause, test, env);
+        }
+        clauses = clauses.cdr()?;
+    }
+    Ok(Step::Value(Obj::new_bool(false, exp.loc.clone())))
+}
+
+fn eval_case(exp: Obj, env: &mut Env) -> EvalResult<Step> {
+    let key = eval(exp.case_key()?, env)?;
+    for clause in exp.case_clauses()?.list_to_vec()? {
+        let chosen = clause.is_cond_else_clause()?
+            || clause
+                .car()?
+                .list_to_vec()?
+                .iter()
+                .any(|datum| datum.strip_syntax().is_eqv(&key));
+        if chosen {
+            return eval_clause(clause, key, env);
+        }
+    }
+    Ok(Step::Value(Obj::new_bool(false, exp.loc.clone())))
+}
+
+// the actions of the chosen clause of a cond or case, value is what
+// chose it and is passed to the receiver of a => clause.
+fn eval_clause(clause: Obj, value: Obj, env: &mut Env) -> EvalResult<Step> {
+    if clause.is_cond_arrow_clause()? {
+        let receiver = eval(clause.cond_receiver()?, env)?;
+        let arguments = Obj::list_from_vec(vec![value], clause.loc.clone());
+        apply_procedure(receiver, arguments, None, clause.loc.clone())
+    } else if clause.cond_actions()?.is_null()? {
+        Ok(Step::Value(value))
+    } else {
+        Ok(Step::Eval(eval_sequence(clause.cond_actions()?, env)?))
+    }
+}
+
+// when runs its body if the test is true, unless if it is false.
+fn eval_when(exp: Obj, env: &mut Env, when: bool) -> EvalResult<Step> {
+    if eval(exp.when_test()?, env)?.is_true() == when {
+        Ok(Step::Eval(eval_sequence(exp.when_actions()?, env)?))
+    } else {
+        Ok(Step::Value(Obj::new_bool(false, exp.loc.clone())))
+    }
+}
+
+// and stops at the first false value, or at the first true one, the
+// last expression is evaluated in tail position. (and) is #t and (or)
+// is #f.
+fn eval_and_or(exp: Obj, env: &mut Env, and: bool) -> EvalResult<Step> {
+    let mut exps = exp.cdr()?;
+    if exps.is_null()? {
+        return Ok(Step::Value(Obj::new_bool(and, exp.loc.clone())));
+    }
+    while !exps.is_last_expr()? {
+        let value = eval(exps.first_expr()?, env)?;
+        if value.is_true() != and {
+            return Ok(Step::Value(value));
+        }
+        exps = exps.rest_expr()?;
+    }
+    Ok(Step::Eval(exps.first_expr()?))
+}
+
+// do ------------------------------------------------------------------------------
+// the loop runs here rather than through eval, so it takes no stack
+// space however many times it goes round. every iteration binds the
+// variables afresh, closures made in the body keep the values of their
+// own iteration.
+
+fn eval_do(exp: Obj, env: &mut Env) -> EvalResult<Step> {
+    let mut vars = vec![];
+    let mut values = vec![];
+    let mut steps = vec![];
+    for binding in exp.do_bindings()?.list_to_vec()? {
+        vars.push(binding.car()?.to_symb()?);
+        values.push(eval(binding.cadr()?, env)?);
+        let step = binding.cddr()?;
+        steps.push(if step.is_null()? {
+            None
+        } else {
+            Some(step.car()?)
+        });
+    }
+    let params = vars
+        .iter()
+        .map(|var| Obj::from_symb(*var, exp.loc.clone()))
+        .collect();
+    let params = Obj::list_from_vec(params, exp.loc.clone());
+    let test_clause = exp.do_test_clause()?;
+    let commands = exp.do_commands()?.list_to_vec()?;
+    loop {
+        let arguments = Obj::list_from_vec(values, exp.loc.clone());
+        let mut loop_env = extend_environment(params.clone(), arguments, env.clone())?;
+        if eval(test_clause.car()?, &mut loop_env)?.is_true() {
+            let results = test_clause.cdr()?;
+            if results.is_null()? {
+                return Ok(Step::Value(Obj::new_bool(false, exp.loc.clone())));
+            }
+            let last = eval_sequence(results, &mut loop_env)?;
+            return Ok(Step::EvalIn(last, loop_env));
+        }
+        for command in commands.iter() {
+            eval(command.clone(), &mut loop_env)?;
+        }
+        values = vec![];
+        for (var, step) in vars.iter().zip(steps.iter()) {
+            values.push(match step {
+                Some(step) => eval(step.clone(), &mut loop_env)?,
+                None => loop_env.lookup_variable_value(var)?,
+            });
+        }
+    }
+}
+
+// macros ------------------------------------------------------------------------
+// a macro use is expanded every time it is evaluated, before eval
+// looks at what kind of expression it is, so a use always means what
+// its macro means now. the expansion is a new object with the
+// location of the use, the use itself is left as it was written.
+//
+// expansions aren't cached, so that is a known cost: a use in the body
+// of a loop or a procedure is matched and expanded again on every
+// iteration or call, and every syntax-rules expansion takes fresh
+// marks for the identifiers it renames.
+
+// the macro exp is a use of, if it is one. the special forms can't be
+// redefined as macros, so their keywords aren't looked up.
+fn macro_use(exp: &Obj, env: &Env) -> Option<Macro> {
+    let keyword = match &*exp.val.borrow() {
+        ObjVal::Cons(head, _) => head.clone(),
+        _ => return None,
+    };
+    match keyword.root_symb() {
+        Some(symb) if !symb.is_well_known() => env.lookup_macro(&keyword),
+        _ => None,
+    }
+}
+
+fn expand_macro_use(exp: &Obj, mac: &Macro, env: &Env) -> EvalResult<Obj> {
+    let mut expansion = syntax::expand(mac, exp, env)?;
+    expansion.loc = exp.loc.clone();
+    Ok(expansion)
+}
+
+fn eval_syntax_rules(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
+    let mac = syntax::syntax_rules(&exp, env)?;
+    Ok(Obj::new(ObjVal::Macro(mac), exp.loc.clone()))
+}
+
+// the transformer of define-syntax or of a binding of let-syntax.
+fn eval_transformer(keyword: &Obj, spec: Obj, env: &mut Env) -> EvalResult<Obj> {
+    let transformer = eval(spec, env)?;
+    let mut mac = transformer.to_macro()?;
+    mac.name.get_or_insert(keyword.to_symb()?);
+    Ok(Obj::new(ObjVal::Macro(mac), transformer.loc.clone()))
+}
+
+// (define-macro (name . params) body ...) binds params to the operands
+// of a use, (define-macro name procedure) passes the whole form.
+fn eval_define_macro(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
+    let target = exp.cadr()?;
+    let (name, procedure, operands) = if target.is_cons() {
+        let params = target.cdr()?;
+        let procedure = Obj::new_closure(params, exp.cddr()?, env.clone(), exp.loc.clone())?;
+        (target.car()?, procedure, true)
+    } else {
+        (target, eval(exp.caddr()?, env)?, false)
+    };
+    if !procedure.is_procedure() {
+        return Err(LazarusError::wrong_type("procedure", &procedure));
+    }
+    let name = name.to_symb()?;
+    procedure.name_procedure(name);
+    let mac = Macro {
+        name: Some(name),
+        transformer: Transformer::Procedure {
+            procedure,
+            operands,
+        },
+        env: env.clone(),
+    };
+    env.define_variable(&name, Obj::new(ObjVal::Macro(mac), exp.loc.clone()));
+    Ok(Obj::from_symb(Symb::OK, exp.loc.clone()))
+}
+
+fn eval_define_syntax(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
+    let keyword = exp.cadr()?;
+    let mac = eval_transformer(&keyword, exp.caddr()?, env)?;
+    env.define_variable(&keyword.to_symb()?, mac);
+    Ok(Obj::from_symb(Symb::OK, exp.loc.clone()))
+}
+
+// the keywords of let-syntax are bound in a new environment around
+// its body, the transformers of letrec-syntax are evaluated in that
+// environment too so they can use each other.
+fn eval_let_syntax(exp: Obj, env: &mut Env, rec: bool) -> EvalResult<Step> {
+    let mut body_env = extend_environment(Obj::nil(None), Obj::nil(None), env.clone())?;
+    for binding in exp.cadr()?.list_to_vec()? {
+        let keyword = binding.car()?;
+        let spec = binding.cadr()?;
+        let mac = if rec {
+            eval_transformer(&keyword, spec, &mut body_env)?
+        } else {
+            eval_transformer(&keyword, spec, env)?
+        };
+        body_env.define_variable(&keyword.to_symb()?, mac);
+    }
+    let last = eval_sequence(exp.cddr()?, &mut body_env)?;
+    Ok(Step::EvalIn(last, body_env))
+}
+
+// quasiquote ------------------------------------------------------------------
+// depth counts the enclosing quasiquotes, only unquotes at depth 1
+// are evaluated, deeper ones are rebuilt with their depth lowered.
+
+fn eval_quasiquote(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
+    quasi(exp.text_of_quotation()?, 1, env)
+}
+
+fn quasi_form(tag: Symb, x: Obj, loc: Option<Loc>) -> Obj {
+    Obj::list_from_vec(vec![Obj::from_symb(tag, loc.clone()), x], loc)
+}
+
+fn quasi(template: Obj, depth: usize, env: &mut Env) -> EvalResult<Obj> {
+    if !template.is_cons() {
+        Ok(template.strip_syntax())
+    } else if template.is_unquoted() {
+        if depth == 1 {
+            eval(template.cadr()?, env)
+        } else {
+            let inner = quasi(template.cadr()?, depth - 1, env)?;
+            Ok(quasi_form(Symb::UNQUOTE, inner, template.loc.clone()))
+        }
+    } else if template.is_quasiquoted() {
+        let inner = quasi(template.cadr()?, depth + 1, env)?;
+        Ok(quasi_form(Symb::QUASIQUOTE, inner, template.loc.clone()))
     } else {
-        eval(exp.if_alternative()?, env)
+        let head = template.car()?;
+        let rest = quasi(template.cdr()?, depth, env)?;
+        if head.is_unquote_splicing() {
+            if depth == 1 {
+                let spliced = eval(head.cadr()?, env)?;
+                if !spliced.is_list() {
+                    return Err(LazarusError::wrong_type("list", &spliced));
+                }
+                let mut list = rest;
+                for x in spliced.list_to_vec()?.into_iter().rev() {
+                    list = Obj::cons(x, list);
+                }
+                Ok(list)
+            } else {
+                let inner = quasi(head.cadr()?, depth - 1, env)?;
+                let head = quasi_form(Symb::UNQUOTE_SPLICING, inner, head.loc.clone());
+                Ok(Obj::cons(head, rest))
+            }
+        } else {
+            Ok(Obj::cons(quasi(head, depth, env)?, rest))
+        }
     }
 }
 
 // drill
-fn make_procedure(parameters: Obj, body: Obj, env: Env) -> Obj {
-    Obj::list_from_vec(
-        vec![
-            Obj::new_symb("procedure".to_owned(), None),
-            parameters,
-            body,
-            Obj::from_env(env, None),
-        ],
-        None,
-    )
+fn make_procedure(parameters: Obj, body: Obj, env: Env, loc: Option<Loc>) -> EvalResult<Obj> {
+    Obj::new_closure(parameters, body, env, loc)
 }
 
+// every clause is a closure over env, the case-lambda applies the
+// first one that takes as many arguments as it is given.
+fn make_case_lambda(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
+    let mut clauses = vec![];
+    for clause in exp.case_lambda_clauses()?.list_to_vec()? {
+        let params = clause.car()?;
+        let body = clause.cdr()?;
+        clauses.push(Closure::new(params, body, env.clone(), clause.loc.clone())?);
+    }
+    Ok(Obj::new_case_lambda(clauses, exp.loc.clone()))
+}
+
+// evaluates every expression but the last and returns the last one,
+// eval runs it in tail position.
 fn eval_sequence(xs: Obj, env: &mut Env) -> EvalResult<Obj> {
-    if xs.is_last_expr()? {
-        eval(xs.car()?, env)
+    let mut xs = xs;
+    while !xs.is_last_expr()? {
+        eval(xs.first_expr()?, env)?;
+        xs = xs.rest_expr()?;
+    }
+    xs.first_expr()
+}
+
+fn list_of_values(exps: Obj, env: &mut Env) -> EvalResult<Obj> {
+    let mut values = vec![];
+    let mut exps = exps;
+    while !exps.has_no_operands()? {
+        values.push(eval(exps.first_operand()?, env)?);
+        exps = exps.rest_operands()?;
+    }
+    let mut list = Obj::empty_list(exps.loc.clone());
+    for value in values.into_iter().rev() {
+        list = Obj::cons(value, list);
+    }
+    Ok(list)
+}
+
+pub fn extend_environment(params: Obj, arguments: Obj, enclosing_env: Env) -> EvalResult<Env> {
+    let frame = Frame::from_var_vals(params, arguments)?;
+    let mut env = Env::new(enclosing_env.id + 1);
+    env.frame = mutcell(frame);
+    env.enclosing = Some(box enclosing_env);
+    Ok(env)
+}
+
+// call stack ------------------------------------------------------------------
+// every application pushes a frame while it runs, the first error
+// to leave an application takes a snapshot of the stack so it can
+// report how execution got there.
+
+thread_local! {
+    static CALL_STACK: RefCell<Vec<CallFrame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// the scheme call stack right now, outermost call first.
+pub fn call_stack() -> Vec<CallFrame> {
+    CALL_STACK.with(|stack| stack.borrow().clone())
+}
+
+fn push_frame(frame: CallFrame) {
+    CALL_STACK.with(|stack| stack.borrow_mut().push(frame));
+}
+
+fn pop_frames(n: usize) {
+    CALL_STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        let len = stack.len();
+        stack.truncate(len - n);
+    });
+}
+
+// a tail call takes over the frame of the call it came from.
+fn replace_frame(frame: CallFrame) {
+    CALL_STACK.with(|stack| {
+        if let Some(top) = stack.borrow_mut().last_mut() {
+            *top = frame;
+        }
+    });
+}
+
+fn with_call_stack(err: LazarusError) -> LazarusError {
+    if err.stack().is_empty() {
+        err.with_stack(call_stack())
     } else {
-        eval(xs.car()?, env)?;
-        eval_sequence(xs.rest_expr()?, env)
+        err
     }
 }
 
-fn apply(procedure: Obj, arguments: Obj) -> EvalResult<Obj> {
+// what is left to do after one step of eval.
+enum Step {
+    Value(Obj),
+    // evaluate this expression in the same environment.
+    Eval(Obj),
+    // evaluate this expression in another environment.
+    EvalIn(Obj, Env),
+    // apply a compound procedure to its arguments.
+    Call(Obj, Obj, CallFrame),
+}
+
+fn eval_application(exp: Obj, env: &mut Env) -> EvalResult<Step> {
+    let operator = exp.operator()?;
+    let procedure = eval(operator.clone(), env)?;
+    let arguments = list_of_values(exp.operands()?, env)?;
+    apply_procedure(
+        procedure,
+        arguments,
+        operator.symbol_name().ok(),
+        exp.loc.clone(),
+    )
+}
+
+// primitives are applied right away, compound procedures are returned
+// for eval to apply in tail position. the call is named after the
+// variable the procedure was called through, or the procedure's name.
+fn apply_procedure(
+    procedure: Obj,
+    arguments: Obj,
+    name: Option<String>,
+    loc: Option<Loc>,
+) -> EvalResult<Step> {
+    let name = name
+        .or_else(|| {
+            procedure
+                .procedure_name()
+                .map(|name| name.name().to_owned())
+        })
+        .unwrap_or_else(|| "<anonymous>".to_owned());
+    let frame = CallFrame {
+        name,
+        loc,
+        args: arguments.list_to_vec()?,
+    };
+    burn_fuel()?;
     if procedure.is_primitive_procedure() {
-        procedure.primitive_apply_to(arguments)
+        let n = frame.args.len();
+        push_frame(frame);
+        let result = procedure
+            .primitive_for(n)
+            .and_then(|primitive| (primitive.func)(arguments))
+            .map_err(with_call_stack);
+        pop_frames(1);
+        Ok(Step::Value(result?))
     } else if procedure.is_compound_procedure() {
-        let env = procedure.environment()?;
-        let mut next_env = extend_environment(procedure.parameters()?, arguments, env.clone())?;
-        eval_sequence(procedure.body()?, &mut next_env)
+        Ok(Step::Call(procedure, arguments, frame))
     } else {
-        Err(format!("Unknown procedure type: APPLY: {:?}", procedure))
+        Err(LazarusError::wrong_type("procedure", &procedure))
     }
 }
 
-fn list_of_values(exps: Obj, env: &mut Env) -> EvalResult<Obj> {
-    if exps.has_no_operands()? {
-        Ok(Obj::empty_list(exps.loc.clone()))
-    } else {
-        Ok(Obj::cons(
-            eval(exps.first_operand()?, env)?,
-            list_of_values(exps.rest_operands()?, env)?,
-        ))
+/// apply a procedure to a list of arguments, for rust code that calls
+/// back into scheme, like the transformers of macros.
+pub fn apply(procedure: Obj, arguments: Obj) -> EvalResult<Obj> {
+    match apply_procedure(procedure, arguments, None, None)? {
+        Step::Call(procedure, arguments, frame) => {
+            push_frame(frame);
+            let result = enter_procedure(procedure, arguments, &None)
+                .and_then(|(body, mut env)| eval(body, &mut env))
+                .map_err(with_call_stack);
+            pop_frames(1);
+            result
+        }
+        Step::Value(value) => Ok(value),
+        _ => unreachable!("apply_procedure returns a value or a call"),
     }
 }
 
-pub fn extend_environment(params: Obj, arguments: Obj, enclosing_env: Env) -> EvalResult<Env> {
-    if params.list_length()? != arguments.list_length()? {
-        Err("params and args need to have same length".to_string())
-    } else {
-        let frame = Frame::from_var_vals(params, arguments)?;
-        let mut env = Env::new(enclosing_env.id + 1);
-        env.frame = mutcell(frame);
-        env.enclosing = Some(box enclosing_env);
-        Ok(env)
+// recursion depth ---------------------------------------------------------------
+// every nested eval counts one level, going past the maximum raises a
+// DepthExceeded error instead of overflowing the rust stack. the stack
+// is grown on the heap when it runs low, so the maximum is what bounds
+// memory, not the size of the thread's stack.
+
+pub const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+// grow the stack by STACK_SEGMENT bytes when less than STACK_RED_ZONE
+// is left, one level of eval takes a few kilobytes in debug builds.
+const STACK_RED_ZONE: usize = 128 * 1024;
+const STACK_SEGMENT: usize = 2 * 1024 * 1024;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    static MAX_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_DEPTH) };
+}
+
+/// set how deeply evaluation may nest on this thread.
+pub fn set_max_depth(depth: usize) {
+    MAX_DEPTH.with(|max| max.set(depth));
+}
+
+pub fn max_depth() -> usize {
+    MAX_DEPTH.with(|max| max.get())
+}
+
+// holds one level of depth for as long as it lives.
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter() -> EvalResult<DepthGuard> {
+        let depth = DEPTH.with(|depth| depth.get());
+        let max = max_depth();
+        if depth >= max {
+            Err(LazarusError::depth_exceeded(max))
+        } else {
+            DEPTH.with(|d| d.set(depth + 1));
+            Ok(DepthGuard)
+        }
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+// the global environment ----------------------------------------------------------
+// the outermost eval on a thread records the global environment of
+// the program it runs, primitives that look at the whole program,
+// like heap-stats, start from it.
+
+thread_local! {
+    static GLOBAL_ENV: RefCell<Option<Env>> = const { RefCell::new(None) };
+}
+
+/// the global environment of the program being evaluated on this
+/// thread, None outside of eval.
+pub fn global_environment() -> Option<Env> {
+    GLOBAL_ENV.with(|global| global.borrow().clone())
+}
+
+// records the global environment of the outermost eval for as long
+// as it lives.
+struct GlobalGuard(bool);
+
+impl GlobalGuard {
+    fn enter(env: &Env) -> GlobalGuard {
+        GLOBAL_ENV.with(|global| {
+            let mut global = global.borrow_mut();
+            if global.is_some() {
+                GlobalGuard(false)
+            } else {
+                *global = Some(env.global());
+                GlobalGuard(true)
+            }
+        })
+    }
+}
+
+impl Drop for GlobalGuard {
+    fn drop(&mut self) {
+        if self.0 {
+            GLOBAL_ENV.with(|global| *global.borrow_mut() = None);
+        }
+    }
+}
+
+// fuel and interrupts -------------------------------------------------------------
+// every step of eval and every application burns one unit of fuel,
+// with no fuel set evaluation runs until it is done. another thread
+// can stop evaluation by setting the interrupt flag.
+
+thread_local! {
+    static FUEL: Cell<Option<u64>> = const { Cell::new(None) };
+    static INTERRUPT: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// give evaluation on this thread a budget of steps, None for no
+/// limit. what is left over stays for the next eval, so a budget can
+/// be topped up and shared by several calls.
+pub fn set_fuel(fuel: Option<u64>) {
+    FUEL.with(|f| f.set(fuel));
+}
+
+/// the fuel left on this thread.
+pub fn fuel() -> Option<u64> {
+    FUEL.with(|f| f.get())
+}
+
+/// evaluation on this thread raises Interrupted at its next step once
+/// the flag is set. the flag is left set, clear it before evaluating
+/// again.
+pub fn set_interrupt(flag: Option<Arc<AtomicBool>>) {
+    INTERRUPT.with(|interrupt| *interrupt.borrow_mut() = flag);
+}
+
+fn burn_fuel() -> EvalResult<()> {
+    let interrupted = INTERRUPT.with(|interrupt| match &*interrupt.borrow() {
+        Some(flag) => flag.load(Ordering::Relaxed),
+        None => false,
+    });
+    if interrupted {
+        return Err(LazarusError::interrupted());
+    }
+    match fuel() {
+        Some(0) => Err(LazarusError::out_of_fuel()),
+        Some(n) => {
+            set_fuel(Some(n - 1));
+            Ok(())
+        }
+        None => Ok(()),
     }
 }
 
@@ -87,57 +626,185 @@ pub fn extend_environment(params: Obj, arguments: Obj, enclosing_env: Env) -> Ev
 //                           |  __/\ V / (_| | |
 //                            \___| \_/ \__,_|_|
 
+// eval loops instead of recursing for expressions in tail position,
+// the branches of an if, the last expression of a begin or a body and
+// the application of a compound procedure, so tail calls run in
+// constant rust stack space.
 pub fn eval(exp: Obj, env: &mut Env) -> EvalResult<Obj> {
+    let _depth = DepthGuard::enter().map_err(with_call_stack)?;
+    let _global = GlobalGuard::enter(env);
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || {
+        // frames this eval pushed, at most one since tail calls replace it.
+        let mut pushed = 0;
+        let result = eval_loop(exp, env, &mut pushed).map_err(with_call_stack);
+        pop_frames(pushed);
+        result
+    })
+}
+
+fn eval_loop(exp: Obj, caller_env: &mut Env, pushed: &mut usize) -> EvalResult<Obj> {
+    let mut exp = exp;
+    // the environment of the procedure body we tail called into.
+    let mut tail_env: Option<Env> = None;
+    loop {
+        let env = match tail_env.as_mut() {
+            Some(env) => env,
+            None => &mut *caller_env,
+        };
+        // errors point at the innermost expression that has a location.
+        let loc = exp.loc.clone();
+        burn_fuel().map_err(|err| err.or_at(&loc))?;
+        match eval_step(exp, env).map_err(|err| err.or_at(&loc))? {
+            Step::Value(value) => return Ok(value),
+            Step::Eval(next) => exp = next,
+            Step::EvalIn(next, next_env) => {
+                exp = next;
+                tail_env = Some(next_env);
+            }
+            Step::Call(procedure, arguments, frame) => {
+                if *pushed == 0 {
+                    push_frame(frame);
+                    *pushed = 1;
+                } else {
+                    replace_frame(frame);
+                }
+                let (body, body_env) = enter_procedure(procedure, arguments, &loc)?;
+                exp = body;
+                tail_env = Some(body_env);
+            }
+        }
+    }
+}
+
+// binds the arguments and evaluates all of the body but its last
+// expression, which is returned with the environment to run it in.
+fn enter_procedure(procedure: Obj, arguments: Obj, loc: &Option<Loc>) -> EvalResult<(Obj, Env)> {
+    let closure = procedure
+        .closure_for(arguments.list_length()?)
+        .map_err(|err| err.or_at(loc))?;
+    let mut env = extend_environment(closure.params.clone(), arguments, closure.env.clone())
+        .map_err(|err| err.or_at(loc))?;
+    let last = eval_sequence(closure.body.clone(), &mut env)?;
+    Ok((last, env))
+}
+
+fn eval_step(exp: Obj, env: &mut Env) -> EvalResult<Step> {
+    // macro use?
+    if let Some(mac) = macro_use(&exp, env) {
+        Ok(Step::Eval(expand_macro_use(&exp, &mac, env)?))
+    }
     // self-evaluating?
-    if exp.is_self_evaluating() {
-        Ok(exp)
+    else if exp.is_self_evaluating() {
+        Ok(Step::Value(exp))
     }
     // variable?
     else if exp.is_variable() {
-        // TODO think about how to better manage symbols.
-        match exp.to_symb() {
-            Ok(sym) => env.lookup_variable_value(&sym),
-            Err(msg) => Err(msg),
-        }
+        Ok(Step::Value(env.lookup_identifier(&exp)?))
     }
     // quoted?
     else if exp.is_quoted() {
-        exp.text_of_quotation()
+        Ok(Step::Value(exp.text_of_quotation()?.strip_syntax()))
+    }
+    // quasiquoted?
+    else if exp.is_quasiquoted() {
+        Ok(Step::Value(eval_quasiquote(exp, env)?))
     }
     // assignment?
     else if exp.is_assignment() {
-        eval_assignment(exp, env)
+        Ok(Step::Value(eval_assignment(exp, env)?))
     }
     // definition?
     else if exp.is_definition() {
-        eval_definition(exp, env)
+        Ok(Step::Value(eval_definition(exp, env)?))
     }
     // if?
     else if exp.is_if() {
-        eval_if(exp, env)
+        Ok(Step::Eval(eval_if(exp, env)?))
     }
     // lambda?
     else if exp.is_lambda() {
-        Ok(make_procedure(
+        Ok(Step::Value(make_procedure(
             exp.lambda_parameters()?,
             exp.lambda_body()?,
             env.clone(),
-        ))
+            exp.loc.clone(),
+        )?))
+    }
+    // cond?
+    else if exp.is_cond() {
+        eval_cond(exp, env)
+    }
+    // case?
+    else if exp.is_case() {
+        eval_case(exp, env)
+    }
+    // when?
+    else if exp.is_when() {
+        eval_when(exp, env, true)
+    }
+    // unless?
+    else if exp.is_unless() {
+        eval_when(exp, env, false)
+    }
+    // and?
+    else if exp.is_and() {
+        eval_and_or(exp, env, true)
+    }
+    // or?
+    else if exp.is_or() {
+        eval_and_or(exp, env, false)
+    }
+    // define-syntax?
+    else if exp.is_define_syntax() {
+        Ok(Step::Value(eval_define_syntax(exp, env)?))
+    }
+    // define-macro?
+    else if exp.is_define_macro() {
+        Ok(Step::Value(eval_define_macro(exp, env)?))
+    }
+    // let-syntax?
+    else if exp.is_let_syntax() {
+        eval_let_syntax(exp, env, false)
+    }
+    // letrec-syntax?
+    else if exp.is_letrec_syntax() {
+        eval_let_syntax(exp, env, true)
+    }
+    // syntax-rules?
+    else if exp.is_syntax_rules() {
+        Ok(Step::Value(eval_syntax_rules(exp, env)?))
+    }
+    // do?
+    else if exp.is_do() {
+        eval_do(exp, env)
+    }
+    // let?
+    else if exp.is_let() {
+        Ok(Step::Eval(exp.let_to_combination()?))
+    }
+    // let*?
+    else if exp.is_let_star() {
+        Ok(Step::Eval(exp.let_star_to_nested_lets()?))
+    }
+    // letrec or letrec*?
+    else if exp.is_letrec() {
+        Ok(Step::Eval(exp.letrec_to_defines()?))
+    }
+    // case-lambda?
+    else if exp.is_case_lambda() {
+        Ok(Step::Value(make_case_lambda(exp, env)?))
     }
     // begin?
     else if exp.is_begin() {
-        eval_sequence(exp.begin_actions()?, env)
+        Ok(Step::Eval(eval_sequence(exp.begin_actions()?, env)?))
     }
     // application?
     else if exp.is_application() {
-        apply(
-            eval(exp.operator()?, env)?,
-            list_of_values(exp.operands()?, env)?,
-        )
+        eval_application(exp, env)
     }
     // uh oh
     else {
-        Err(format!("Unknown expression types: {:?}", exp))
+        Err(LazarusError::eval(&format!("unknown expression type: {:?}", exp)).with_obj(&exp))
     }
 }
 
@@ -152,7 +819,7 @@ mod tests {
 
     fn get_parser(s: &str) -> Parser {
         let lexer = Lexer::new(s, "test.scm");
-        Parser::new(lexer)
+        Parser::new(lexer).unwrap()
     }
 
     fn eval_str(s: &str) -> EvalResult<Obj> {
@@ -185,7 +852,563 @@ mod tests {
 "#;
         let result = eval_str(prog);
         //assert!(result.is_ok());
-        assert_eq!(result, Err("undefined variable: z".to_string()));
+        match result {
+            Err(LazarusError::UnboundVariable { name, .. }) => assert_eq!(name, "z"),
+            _ => panic!("expected z to be unbound, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn eval_error_kinds() {
+        match eval_str("(car 1)") {
+            Err(LazarusError::WrongType { expected, ctx }) => {
+                assert_eq!(expected, "pair");
+                assert_eq!(ctx.obj, Some(Obj::new_int(1, None)));
+            }
+            result => panic!("expected a wrong type error, got: {:?}", result),
+        }
+        match eval_str("(cons 1)") {
+            Err(LazarusError::Arity { procedure, got, .. }) => {
+                assert_eq!(procedure, "cons");
+                assert_eq!(got, 1);
+            }
+            result => panic!("expected an arity error, got: {:?}", result),
+        }
+        // primitives are checked against their arity before they run.
+        match eval_str("(dec 1 2)") {
+            Err(LazarusError::Arity { expected, got, .. }) => {
+                assert_eq!(expected, "1");
+                assert_eq!(got, 2);
+            }
+            result => panic!("expected an arity error, got: {:?}", result),
+        }
+        match eval_str("(string=?)") {
+            Err(LazarusError::Arity { expected, got, .. }) => {
+                assert_eq!(expected, "at least 1");
+                assert_eq!(got, 0);
+            }
+            result => panic!("expected an arity error, got: {:?}", result),
+        }
+        match eval_str("((lambda (x y) x) 1)") {
+            Err(LazarusError::Arity { expected, got, .. }) => {
+                assert_eq!(expected, "2");
+                assert_eq!(got, 1);
+            }
+            result => panic!("expected an arity error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn eval_user_error() {
+        let result = eval_str(r#"(begin (error "bad thing:" 1 'x) 2)"#);
+        match &result {
+            Err(err @ LazarusError::User { msg, .. }) => {
+                assert_eq!(msg, "bad thing:");
+                assert_eq!(err.to_string(), "bad thing: 1 'x");
+            }
+            _ => panic!("expected a user error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn eval_error_has_innermost_loc() {
+        // the unbound y is at bytes 13..14.
+        let err = eval_str("(begin (+ 1 (y)))").unwrap_err();
+        let loc = err.loc().unwrap();
+        assert_eq!((loc.start, loc.end), (13, 14));
+    }
+
+    #[test]
+    fn eval_error_call_stack() {
+        let prog = r#"
+(begin
+  (define inner (lambda (x) (car x)))
+  (define outer (lambda (x) (+ 1 (inner (+ x 1)))))
+  (outer 1))
+"#;
+        let err = eval_str(prog).unwrap_err();
+        let names: Vec<&str> = err.stack().iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["outer", "inner", "car"]);
+        assert_eq!(err.stack()[1].args, vec![Obj::new_int(2, None)]);
+        assert!(err
+            .backtrace()
+            .starts_with("in car at test.scm:3:29, called from inner at test.scm:4:34"));
+        // the stack is unwound once the error is out.
+        assert!(call_stack().is_empty());
+    }
+
+    #[test]
+    fn eval_tail_calls() {
+        // far deeper than the rust stack allows without tail calls, even
+        // and odd call each other in tail position through an if and a
+        // begin.
+        let prog = r#"
+(begin
+  (define even (lambda (n) (if (> n 0) (begin (odd (- n 1))) (quote even))))
+  (define odd (lambda (n) (if (> n 0) (even (- n 1)) (quote odd))))
+  (even 100000))
+"#;
+        let result = eval_str(prog).unwrap();
+        assert_eq!(result.symbol_name().unwrap(), "even");
+        assert!(call_stack().is_empty());
+    }
+
+    #[test]
+    fn eval_out_of_fuel() {
+        let prog = "(begin (define spin (lambda () (spin))) (spin))";
+        set_fuel(Some(10_000));
+        let result = eval_str(prog);
+        assert_eq!(fuel(), Some(0));
+        match &result {
+            Err(err @ LazarusError::OutOfFuel { .. }) => {
+                assert_eq!(err.stack()[0].name, "spin");
+            }
+            _ => panic!("expected to run out of fuel, got: {:?}", result),
+        }
+
+        // a small budget is enough for a small program, the rest is
+        // left for later.
+        set_fuel(Some(100));
+        assert_eq!(eval_str("(+ 1 2)").unwrap(), Obj::new_int(3, None));
+        let left = fuel().unwrap();
+        assert!(left > 0 && left < 100);
+        set_fuel(None);
+    }
+
+    #[test]
+    fn eval_interrupt() {
+        let flag = Arc::new(AtomicBool::new(false));
+        set_interrupt(Some(flag.clone()));
+        let setter = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            flag.store(true, Ordering::Relaxed);
+        });
+        let result = eval_str("(begin (define spin (lambda () (spin))) (spin))");
+        setter.join().unwrap();
+        set_interrupt(None);
+        match result {
+            Err(LazarusError::Interrupted { .. }) => (),
+            _ => panic!("expected to be interrupted, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn eval_cycles_are_collected() {
+        // monster3 calls mkCycle 50 * 100 * 100 times, every call makes a
+        // frame that a closure defined in it captures. without a tracing
+        // collector none of those frames were ever freed.
+        let prog = std::fs::read_to_string("test-cases/cycle.scm").unwrap();
+        let before = FRAMES_FREED.with(|n| n.get());
+        let result = eval_str(&prog).unwrap();
+        assert_eq!(result.symbol_name().unwrap(), "ok");
+        gc::force_collect();
+        let freed = FRAMES_FREED.with(|n| n.get()) - before;
+        assert!(freed >= 50 * 100 * 100, "only {} frames were freed", freed);
+    }
+
+    #[test]
+    fn eval_heap_forms() {
+        let prog = r#"
+(begin
+  (define f (lambda () 1))
+  (define stats heap-stats)
+  (list (heap-cycles) (stats) (heap-stats (list 1 2)) (heap-cycles (list f))))
+"#;
+        let result = eval_str(prog).unwrap().list_to_vec().unwrap();
+        assert_eq!(format!("{:?}", result[0]), "('f . ())");
+        let stats = result[1].list_to_vec().unwrap();
+        assert_eq!(stats[0].car().unwrap().symbol_name().unwrap(), "objects");
+        assert_eq!(format!("{:?}", stats[2]), "('frames . 1)");
+        // from an object, what it keeps alive.
+        let stats = result[2].list_to_vec().unwrap();
+        assert_eq!(
+            format!("{:?}", stats[0]),
+            "('objects . (('cons . 2) . (('int . 2) . (('nil . 1) . ()))))"
+        );
+        assert_eq!(format!("{:?}", stats[2]), "('frames . 0)");
+        assert_eq!(format!("{:?}", result[3]), "('f . ())");
+    }
+
+    #[test]
+    fn eval_equivalence_predicates() {
+        let prog = r#"
+(begin
+  (define xs (list 1 2))
+  (list (eq? xs xs) (eq? (list 1 2) (list 1 2)) (equal? (list 1 2) xs)
+        (eq? 'a 'a) (eqv? 1.5 1.5) (eq? "s" "s") (equal? "s" "s")))
+"#;
+        let result = eval_str(prog).unwrap();
+        let bools: Vec<bool> = result
+            .list_to_vec()
+            .unwrap()
+            .iter()
+            .map(|b| b.is_true())
+            .collect();
+        assert_eq!(bools, vec![true, false, true, true, true, false, true]);
+    }
+
+    #[test]
+    fn eval_procedure_objects() {
+        let prog = r#"
+(begin
+  (define (fact n)
+    (if (< n 2) 1 (* n (fact (- n 1)))))
+  (define alias fact)
+  (list fact car (lambda (x) x) (procedure? alias) (procedure? car)
+        (procedure? '(procedure (x) x))))
+"#;
+        let xs = eval_str(prog).unwrap().list_to_vec().unwrap();
+        assert_eq!(format!("{:?}", xs[0]), "#<procedure fact test.scm:3>");
+        assert_eq!(format!("{:?}", xs[1]), "#<procedure car>");
+        assert_eq!(format!("{:?}", xs[2]), "#<procedure test.scm:6>");
+        assert!(xs[3].is_true() && xs[4].is_true() && !xs[5].is_true());
+        // a list that looks like a closure is not one.
+        match eval_str("('(procedure (x) x) 1)") {
+            Err(LazarusError::WrongType { expected, .. }) => assert_eq!(expected, "procedure"),
+            result => panic!("expected a wrong type error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn eval_rest_parameters() {
+        let prog = r#"
+(begin
+  (define (my-list . xs) xs)
+  (define (tail a b . rest) rest)
+  (define (length xs) (if (null? xs) 0 (+ 1 (length (cdr xs)))))
+  (define count (lambda args (length args)))
+  (list (my-list) (my-list 1 2) (tail 1 2) (tail 1 2 3 4) (count 'a 'b 'c)))
+"#;
+        let expected = eval_str("(quote (() (1 2) () (3 4) 3))").unwrap();
+        assert_eq!(eval_str(prog).unwrap(), expected);
+        match eval_str("(begin (define (tail a b . rest) rest) (tail 1))") {
+            Err(LazarusError::Arity {
+                procedure,
+                expected,
+                got,
+                ..
+            }) => {
+                assert_eq!(procedure, "tail");
+                assert_eq!(expected, "at least 2");
+                assert_eq!(got, 1);
+            }
+            result => panic!("expected an arity error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn eval_case_lambda() {
+        let prog = r#"
+(begin
+  (define area
+    (case-lambda
+      ((r) (* 3 (* r r)))
+      ((w h) (* w h))
+      ((w h . more) (cons (* w h) more))))
+  (list (area 2) (area 2 3) (area 2 3 4 5) area))
+"#;
+        let xs = eval_str(prog).unwrap().list_to_vec().unwrap();
+        assert_eq!(xs[0], Obj::new_int(12, None));
+        assert_eq!(xs[1], Obj::new_int(6, None));
+        assert_eq!(xs[2], eval_str("(quote (6 4 5))").unwrap());
+        assert_eq!(format!("{:?}", xs[3]), "#<procedure area test.scm:4>");
+        match eval_str("(begin (define f (case-lambda ((a) a) ((a b) b))) (f))") {
+            Err(LazarusError::Arity {
+                procedure,
+                expected,
+                got,
+                ..
+            }) => {
+                assert_eq!(procedure, "f");
+                assert_eq!(expected, "1 or 2");
+                assert_eq!(got, 0);
+            }
+            result => panic!("expected an arity error, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn eval_let_forms() {
+        let prog = r#"
+(begin
+  (define x 10)
+  (list (let ((x 1) (y x)) (+ x y))
+        (let* ((x 1) (y (+ x 1))) (* x y))
+        (let* () x)
+        (letrec ((even? (lambda (n) (if (< n 1) #t (odd? (- n 1)))))
+                 (odd? (lambda (n) (if (< n 1) #f (even? (- n 1))))))
+          (even? 10))
+        (letrec* ((a 2) (b (* a 3))) b)
+        x))
+"#;
+        let result = eval_str(prog).unwrap().list_to_vec().unwrap();
+        assert_eq!(result[0], Obj::new_int(11, None));
+        assert_eq!(result[1], Obj::new_int(2, None));
+        assert_eq!(result[2], Obj::new_int(10, None));
+        assert!(result[3].is_true());
+        assert_eq!(result[4], Obj::new_int(6, None));
+        assert_eq!(result[5], Obj::new_int(10, None));
+    }
+
+    #[test]
+    fn eval_named_let_loops() {
+        // far more iterations than the depth limit allows, the loop
+        // calls itself in tail position.
+        let prog = r#"
+(let loop ((i 0) (acc 0))
+  (if (< i 100000)
+      (loop (+ i 1) (+ acc 2))
+      acc))
+"#;
+        set_max_depth(1000);
+        let result = eval_str(prog);
+        set_max_depth(DEFAULT_MAX_DEPTH);
+        assert_eq!(result.unwrap(), Obj::new_int(200000, None));
+        assert!(call_stack().is_empty());
+    }
+
+    #[test]
+    fn eval_conditionals() {
+        let prog = r#"
+(begin
+  (define (classify n)
+    (cond ((< n 0) 'negative)
+          ((and (> n 100) n) => (lambda (big) (list 'big big)))
+          ((> n 0) 'positive)
+          (else 'zero)))
+  (define (kind x)
+    (case x
+      ((1 2 3) 'small)
+      ((#\a a) 'letter)
+      (else => (lambda (y) (cons 'other y)))))
+  (list (classify -5) (classify 200) (classify 7) (classify 0)
+        (kind 2) (kind 'a) (kind 9)
+        (when (< 1 2) 'a 'b) (unless (< 1 2) (error "unreachable"))
+        (and) (and 1 2) (and 1 #f (error "unreachable"))
+        (or) (or #f 3) (or #f #f)
+        (cond ((cons 'b 2) => cdr) (else 'none))))
+"#;
+        let result = eval_str(prog).unwrap();
+        let expected = r#"('negative . (('big . (200 . ())) . ('positive . ('zero . ('small . ('letter . (('other . 9) . ('b . (false . (true . (2 . (false . (false . (3 . (false . (2 . ()))))))))))))))))"#;
+        assert_eq!(format!("{:?}", result), expected);
+    }
+
+    #[test]
+    fn eval_conditionals_tail_calls() {
+        let prog = r#"
+(begin
+  (define (count-down n)
+    (cond ((< n 1) 'done)
+          (else (and #t (or #f (when #t (case 1 ((1) (count-down (- n 1))))))))))
+  (count-down 100000))
+"#;
+        set_max_depth(1000);
+        let result = eval_str(prog);
+        set_max_depth(DEFAULT_MAX_DEPTH);
+        assert_eq!(result.unwrap().symbol_name().unwrap(), "done");
+    }
+
+    #[test]
+    fn eval_do_loops() {
+        let prog = r#"
+(do ((i 0 (+ i 1))
+     (acc '() (cons i acc))
+     (fixed 'x))
+    ((> i 4) (cons fixed acc)))
+"#;
+        let expected = eval_str("(quote (x 4 3 2 1 0))").unwrap();
+        assert_eq!(eval_str(prog).unwrap(), expected);
+
+        // every iteration has its own i.
+        let prog = r#"
+(do ((i 0 (+ i 1))
+     (fs '() (cons (lambda () i) fs)))
+    ((> i 2) (list ((car fs)) ((car (cdr fs))))))
+"#;
+        let expected = eval_str("(quote (2 1))").unwrap();
+        assert_eq!(eval_str(prog).unwrap(), expected);
+    }
+
+    #[test]
+    fn eval_do_constant_space() {
+        let prog = r#"
+(do ((i 0 (+ i 1))
+     (total 0 (+ total i)))
+    ((> i 99999) total)
+  (list i total))
+"#;
+        set_max_depth(1000);
+        let result = eval_str(prog);
+        set_max_depth(DEFAULT_MAX_DEPTH);
+        assert_eq!(result.unwrap(), Obj::new_int(4999950000, None));
+    }
+
+    #[test]
+    fn eval_syntax_rules() {
+        let prog = r#"
+(begin
+  (define-syntax add-ten
+    (syntax-rules ()
+      ((_ e) (let ((tmp 10)) (+ tmp e)))))
+  (define-syntax my-or
+    (syntax-rules ()
+      ((_) #f)
+      ((_ e) e)
+      ((_ e r ...) (let ((t e)) (if t t (my-or r ...))))))
+  (define-syntax my-list
+    (syntax-rules ()
+      ((_ x ...) (list 'my-list x ...))))
+  (define-syntax after-countdown
+    (syntax-rules ()
+      ((_ n body) (let loop ((i n)) (if (< i 1) body (loop (- i 1)))))))
+  (list (let ((tmp 1)) (add-ten tmp))
+        (let ((t 5)) (my-or #f t))
+        (let ((list car)) (my-list 1 2))
+        (let ((loop 'user)) (after-countdown 3 loop))))
+"#;
+        let expected = eval_str("(quote (11 5 (my-list 1 2) user))").unwrap();
+        assert_eq!(eval_str(prog).unwrap(), expected);
+    }
+
+    #[test]
+    fn eval_syntax_rules_literals() {
+        // a literal matches an identifier bound where it is bound in the
+        // definition of the macro, a locally bound else is a different one.
+        let prog = r#"
+(begin
+  (define-syntax my-cond
+    (syntax-rules (else)
+      ((_ (else e)) (list 'else e))
+      ((_ (c e)) (list 'test e))))
+  (list (my-cond (else 1))
+        (let ((else #f)) (my-cond (else 2)))))
+"#;
+        let expected = eval_str("(quote ((else 1) (test 2)))").unwrap();
+        assert_eq!(eval_str(prog).unwrap(), expected);
+    }
+
+    #[test]
+    fn eval_let_syntax() {
+        let prog = r#"
+(begin
+  (define (f x) (list 'outer x))
+  (list
+    (let-syntax ((f (syntax-rules () ((_ x) (list 'macro x))))
+                 (g (syntax-rules () ((_ x) (f x)))))
+      (list (f 1) (g 2)))
+    (letrec-syntax ((ev? (syntax-rules () ((_) #t) ((_ x . r) (od? . r))))
+                    (od? (syntax-rules () ((_) #f) ((_ x . r) (ev? . r)))))
+      (ev? 1 2 3 4))))
+"#;
+        let result = eval_str(prog).unwrap();
+        let expected = eval_str("(quote (((macro 1) (outer 2))))").unwrap();
+        assert_eq!(result.car().unwrap(), expected.car().unwrap());
+        assert!(result.cadr().unwrap().is_true());
+        match eval_str("(begin (define-syntax m (syntax-rules () ((_ x) x))) (m))") {
+            Err(err @ LazarusError::Eval { .. }) => {
+                assert_eq!(err.to_string(), "no syntax-rules pattern of m matches");
+            }
+            result => panic!("expected no rule to match, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn eval_define_macro() {
+        let prog = r#"
+(begin
+  (define-macro (my-unless test . body)
+    `(if ,test #f (begin ,@body)))
+  (define-macro swap-args
+    (lambda (form) (list (car (cdr form)) (car (cdr (cdr (cdr form)))) (car (cdr (cdr form))))))
+  (define-macro (my-or2 a b)
+    (let ((t (gensym)))
+      `(let ((,t ,a)) (if ,t ,t ,b))))
+  (define-macro (with-it e body) `(let ((it ,e)) ,body))
+  (define t 5)
+  (list (my-unless #f 1 2)
+        (swap-args - 1 10)
+        (my-or2 #f t)
+        (with-it 7 (+ it 1))
+        (eq? (gensym) (gensym))))
+"#;
+        let result = eval_str(prog).unwrap().list_to_vec().unwrap();
+        let expected = eval_str("(quote (2 9 5 8))").unwrap();
+        assert_eq!(result[..4], expected.list_to_vec().unwrap()[..]);
+        assert!(!result[4].is_true());
+        let g = eval_str("(begin (symbol->string (gensym \"tmp\")))").unwrap();
+        assert!(format!("{:?}", g).starts_with("\"tmp"));
+        assert_ne!(
+            g,
+            eval_str("(begin (symbol->string (gensym \"tmp\")))").unwrap()
+        );
+
+        // a transformer can return its own form, the use isn't changed.
+        let prog = "(begin (define-macro m (lambda (form) (list 'quote form))) (m 1))";
+        assert_eq!(eval_str(prog).unwrap(), eval_str("(quote (m 1))").unwrap());
+
+        // uses mean what the macro means when they are evaluated.
+        let prog = r#"
+(begin
+  (define-macro (two) 2)
+  (define f (lambda () (two)))
+  (define a (f))
+  (define-macro (two) 3)
+  (list a (f)))
+"#;
+        assert_eq!(eval_str(prog).unwrap(), eval_str("(quote (2 3))").unwrap());
+
+        // the expansion can be data of the program, it is left alone.
+        // heap-stats makes its list without locations.
+        let prog = r#"
+(begin
+  (define data (heap-stats 1))
+  (define-macro (m) (list 'quote data))
+  (m))
+"#;
+        let (result, env) = eval_str_env(prog);
+        let data = env.lookup_variable_value(&Symb::intern("data")).unwrap();
+        assert!(result.unwrap().is_same(&data));
+        assert!(data.cdr().unwrap().loc.is_none());
+
+        // errors in the expansion point at the use of the macro.
+        let prog = "(begin (define-macro (bad) (list 'car 1)) (bad))";
+        let err = eval_str(prog).unwrap_err();
+        let loc = err.loc().unwrap();
+        assert_eq!(loc.start, prog.rfind("(bad)").unwrap());
+    }
+
+    #[test]
+    fn eval_er_macro_transformer() {
+        let prog = r#"
+(begin
+  (define-syntax my-if
+    (er-macro-transformer
+      (lambda (form rename compare)
+        (let ((test (car (cdr form))) (then (car (cdr (cdr form)))))
+          (if (compare test (rename 'else))
+              then
+              `(,(rename 'let) ((,(rename 'tmp) ,test))
+                  (,(rename 'if) ,(rename 'tmp) ,then (,(rename 'list) 0))))))))
+  (define tmp 'outer)
+  (list (my-if else 1)
+        (my-if #t tmp)
+        (let ((list car)) (my-if #f 2))))
+"#;
+        let expected = eval_str("(quote (1 outer (0)))").unwrap();
+        assert_eq!(eval_str(prog).unwrap(), expected);
+        // rename only works while its expansion runs.
+        let prog = r#"
+(begin
+  (define-syntax m (er-macro-transformer (lambda (f rename c) (list 'quote rename))))
+  ((m) 'x))
+"#;
+        match eval_str(prog) {
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "rename called outside of a macro expansion"
+            ),
+            result => panic!("expected rename to fail, got: {:?}", result),
+        }
     }
 
     #[test]
@@ -197,16 +1420,36 @@ mod tests {
         assert_eq!(result.unwrap(), Obj::new_int(4, None));
     }
 
-    //#[test]
+    #[test]
     fn test_define_2() {
-        // expect stack overflow
-        // let prog = "(begin (define fact (lambda (x) (fact x))) (fact 3) 4)";
-        // let (result, env) = eval_str_env(prog);
-        // assert!(env.frame.borrow().all_names().contains(&"fact".to_string()));
-        // println!("env:    {:?}", env.frame.borrow().all_names());
-        // println!("result: {:?}", result);
-        //assert!(result.is_ok());
-        //assert_eq!(result.unwrap(), Obj::new_int(4, None));
+        // fact never returns, the depth guard stops it.
+        set_max_depth(1000);
+        let prog = "(begin (define fact (lambda (x) (+ 1 (fact x)))) (fact 3) 4)";
+        let (result, env) = eval_str_env(prog);
+        set_max_depth(DEFAULT_MAX_DEPTH);
+        assert!(env.frame.borrow().all_names().contains(&"fact".to_string()));
+        match result {
+            Err(err @ LazarusError::DepthExceeded { .. }) => {
+                assert_eq!(err.to_string(), "maximum recursion depth exceeded: 1000");
+                assert!(err.stack().len() > 100);
+                assert!(err.stack().iter().all(|frame| frame.name == "fact"));
+            }
+            _ => panic!("expected the depth to be exceeded, got: {:?}", result),
+        }
+        // the guard unwinds with the error, evaluation works again.
+        assert!(call_stack().is_empty());
+        assert_eq!(eval_str("(+ 1 2)").unwrap(), Obj::new_int(3, None));
+    }
+
+    #[test]
+    fn eval_deep_recursion() {
+        // deeper than the 2MB stack of a test thread would allow.
+        let prog = r#"
+(begin
+  (define count (lambda (n) (if (> n 0) (+ 1 (count (- n 1))) 0)))
+  (count 5000))
+"#;
+        assert_eq!(eval_str(prog).unwrap(), Obj::new_int(5000, None));
     }
 
     #[test]
@@ -259,6 +1502,118 @@ mod tests {
         assert_eq!(result.unwrap(), Obj::new_int(1, None));
     }
 
+    #[test]
+    fn eval_string_self_evaluating() {
+        let result = eval_str(r#"(begin "abc")"#).unwrap();
+        assert_eq!(result, Obj::new_str("abc".to_owned(), None));
+    }
+
+    #[test]
+    fn eval_string_primitives() {
+        let prog = r#"(string-append "foo" (substring "xbarx" 1 4) (symbol->string (quote baz)))"#;
+        assert_eq!(
+            eval_str(prog).unwrap(),
+            Obj::new_str("foobarbaz".to_owned(), None)
+        );
+        assert_eq!(
+            eval_str(r#"(string-length "λx")"#).unwrap(),
+            Obj::new_int(2, None)
+        );
+        assert_eq!(
+            eval_str(r#"(string=? "ab" (string-append "a" "b") "ab")"#).unwrap(),
+            Obj::new_bool(true, None)
+        );
+        assert_eq!(
+            eval_str(r#"(string->symbol "abc")"#).unwrap(),
+            Obj::new_symb("abc", None)
+        );
+        assert!(eval_str(r#"(string-ref "abc" 3)"#).is_err());
+    }
+
+    #[test]
+    fn eval_string_mutable_cell() {
+        let (result, env) = eval_str_env(r#"(begin (define s "abc") s)"#);
+        if let ObjVal::Str(s) = &mut *result.unwrap().val.borrow_mut() {
+            s.push('d');
+        }
+        let sym = Symb::intern("s");
+        let s = env.lookup_variable_value(&sym).unwrap();
+        assert_eq!(s, Obj::new_str("abcd".to_owned(), None));
+    }
+
+    #[test]
+    fn eval_char_primitives() {
+        assert_eq!(
+            eval_str(r#"(string-ref "aλc" 1)"#).unwrap(),
+            Obj::new_char('λ', None)
+        );
+        assert_eq!(
+            eval_str(r"(char->integer #\x3bb)").unwrap(),
+            Obj::new_int(0x3bb, None)
+        );
+        assert_eq!(
+            eval_str(r"(char-upcase (integer->char 97))").unwrap(),
+            Obj::new_char('A', None)
+        );
+        assert_eq!(
+            eval_str(r"(char<? #\a #\b #\c)").unwrap(),
+            Obj::new_bool(true, None)
+        );
+        assert_eq!(
+            eval_str(r"(char-ci=? #\a #\A)").unwrap(),
+            Obj::new_bool(true, None)
+        );
+        assert_eq!(
+            eval_str(r"(char-alphabetic? #\车)").unwrap(),
+            Obj::new_bool(true, None)
+        );
+        assert_eq!(
+            eval_str(r"(char? #\space)").unwrap(),
+            Obj::new_bool(true, None)
+        );
+    }
+
+    #[test]
+    fn eval_quote_abbreviation() {
+        let result = eval_str("(car '(a b))").unwrap();
+        assert_eq!(result, Obj::new_symb("a", None));
+    }
+
+    #[test]
+    fn eval_quasiquote_unquote() {
+        let result = eval_str("(begin `(1 ,(+ 1 1) ,@(list 3 4) 5))").unwrap();
+        let expected = eval_str("(begin '(1 2 3 4 5))").unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn eval_quasiquote_dotted_unquote() {
+        // `(1 . ,x) reads as (1 unquote x)
+        let result = eval_str("(begin (define x 2) `(1 . ,x))").unwrap();
+        let expected = Obj::cons(Obj::new_int(1, None), Obj::new_int(2, None));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn eval_quoted_dotted_pair() {
+        let result = eval_str("(cdr '(1 . 2))").unwrap();
+        assert_eq!(result, Obj::new_int(2, None));
+    }
+
+    #[test]
+    fn eval_quasiquote_nested() {
+        let result = eval_str("(begin `(a `(b ,(c ,(+ 1 3)) ,@(d))))").unwrap();
+        let expected =
+            eval_str("(begin '(a (quasiquote (b (unquote (c 4)) (unquote-splicing (d))))))")
+                .unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn eval_quasiquote_splice_not_list() {
+        assert!(eval_str("(begin `(1 ,@2))").is_err());
+    }
+
     #[test]
     fn test_empty_list() {
         let prog = "(list)";
@@ -402,7 +1757,7 @@ mod tests {
     #[test]
     fn eval_definition_1() {
         let mut env = Env::new(0);
-        let sym = Symb::new("foo", "test-eval.rs".to_owned(), 0);
+        let sym = Symb::intern("foo");
         let mut parser = get_parser("(define foo 42)");
         let parse_results = parser.list().unwrap();
         let obj = parse_results.to_obj();
@@ -416,7 +1771,7 @@ mod tests {
     #[test]
     fn eval_assign_1() {
         let mut env = Env::new(0);
-        let sym = Symb::new("foo", "test-eval.rs".to_owned(), 0);
+        let sym = Symb::intern("foo");
         env.define_variable(&sym, Obj::new_int(123, None));
 
         let mut parser = get_parser("(set! foo 42)");
@@ -434,7 +1789,7 @@ mod tests {
     fn eval_assign_2() {
         let mut env1 = Env::new(0);
         let mut env2 = Env::new(1);
-        let sym = Symb::new("foo", "test-eval.rs".to_owned(), 0);
+        let sym = Symb::intern("foo");
 
         env1.define_variable(&sym, Obj::new_int(123, None));
         env2.enclosing = Some(box env1);
@@ -469,7 +1824,7 @@ mod tests {
     fn eval_lookup_variable_1() {
         let obj = Obj::new_int(128, None);
         let mut env = Env::new(0);
-        let sym = Symb::new("x", "test-eval.rs".to_owned(), 42);
+        let sym = Symb::intern("x");
         env.define_variable(&sym, obj.clone());
         let obj2 = env.lookup_variable_value(&sym).unwrap();
         assert_eq!(obj, obj2);
@@ -480,7 +1835,7 @@ mod tests {
         let mut parser = get_parser("1");
         let results = parser.expr();
         match results {
-            Err(msg) => panic!(msg),
+            Err(msg) => panic!("{}", msg),
             Ok(Ast::Leaf(Token { tok, .. })) => match tok {
                 Tok::Int(n) => assert_eq!(n, 1),
                 x => panic!("should have got a 1, got: {:?}", x),
@@ -8,19 +8,25 @@ impl Frame {
         }
     }
 
+    /// bind parameters to arguments. params is a list of symbols, a
+    /// dotted list (a b . rest) or a single symbol, the symbol after the
+    /// dot or on its own is bound to the list of the remaining arguments.
     pub fn from_var_vals(mut params: Obj, mut arguments: Obj) -> EvalResult<Frame> {
-        if params.list_length()? != arguments.list_length()? {
-            Err("Frame got bad constructor params, unmatched lengths".to_string())
-        } else {
-            let mut frame = Frame::new();
-
-            while !params.is_null()? {
-                frame.insert(params.car()?.to_symb()?, arguments.car()?);
-                params = params.cdr()?;
-                arguments = arguments.cdr()?;
-            }
-            Ok(frame)
+        let arity = Arity::of_params(&params)?;
+        let got = arguments.list_length()?;
+        if !arity.accepts(got) {
+            return Err(LazarusError::arity("lambda", &arity.to_string(), got));
+        }
+        let mut frame = Frame::new();
+        while params.is_cons() {
+            frame.insert(params.car()?.to_symb()?, arguments.car()?);
+            params = params.cdr()?;
+            arguments = arguments.cdr()?;
+        }
+        if params.is_symbol() {
+            frame.insert(params.to_symb()?, arguments);
         }
+        Ok(frame)
     }
 
     pub fn insert(&mut self, sym: Symb, obj: Obj) {
@@ -36,7 +42,10 @@ impl Frame {
     }
 
     pub fn all_names(&self) -> Vec<String> {
-        self.symbol_table.keys().map(|s| s.name.clone()).collect()
+        self.symbol_table
+            .keys()
+            .map(|s| s.name().to_owned())
+            .collect()
     }
 }
 
@@ -47,14 +56,38 @@ mod tests {
     #[test]
     fn frame_1() {
         let mut f = Frame::new();
-        let sym1 = Symb::new("a", "test.scm".to_owned(), 0);
+        let sym1 = Symb::intern("a");
         let obj1 = Obj::new_int(42, None);
 
-        f.insert(sym1.clone(), obj1);
+        f.insert(sym1, obj1);
         let obj1cell: Obj = f.get(&sym1).unwrap();
         let obj2cell: Obj = f.get(&sym1).unwrap();
         let newval = ObjVal::Int(43);
         *obj1cell.val.borrow_mut() = newval.clone();
         assert_eq!(*obj2cell.val.borrow(), newval.clone());
     }
+
+    #[test]
+    fn frame_rest_parameter() {
+        let (a, rest) = (Symb::intern("a"), Symb::intern("rest"));
+        let ints = |ns: &[i64]| {
+            Obj::list_from_vec(ns.iter().map(|n| Obj::new_int(*n, None)).collect(), None)
+        };
+        let params = Obj::cons(Obj::from_symb(a, None), Obj::from_symb(rest, None));
+
+        let f = Frame::from_var_vals(params.clone(), ints(&[1, 2, 3])).unwrap();
+        assert_eq!(f.get(&a), Some(Obj::new_int(1, None)));
+        assert_eq!(f.get(&rest), Some(ints(&[2, 3])));
+        let f = Frame::from_var_vals(params.clone(), ints(&[1])).unwrap();
+        assert_eq!(f.get(&rest), Some(ints(&[])));
+        match Frame::from_var_vals(params, ints(&[])) {
+            Err(LazarusError::Arity { expected, got, .. }) => {
+                assert_eq!((expected.as_str(), got), ("at least 1", 0));
+            }
+            result => panic!("expected an arity error, got: {:?}", result),
+        }
+
+        let f = Frame::from_var_vals(Obj::from_symb(rest, None), ints(&[1, 2])).unwrap();
+        assert_eq!(f.get(&rest), Some(ints(&[1, 2])));
+    }
 }
@@ -0,0 +1,275 @@
+use crate::types::*;
+use gc::GcCell;
+use std::collections::{BTreeMap, HashSet};
+
+// Heap statistics. The collector doesn't let us look at its heap, so
+// the stats walk everything reachable from an environment instead:
+// the frames of the environment and its enclosing ones, the objects
+// bound in them, and everything those objects point to. That is what
+// the collector keeps alive for a program running in that environment.
+// They can walk from an object too, what it keeps alive.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeapStats {
+    /// live objects by the kind of value they hold.
+    pub objects: BTreeMap<&'static str, usize>,
+    /// environments, the one walked from and those captured by
+    /// closures.
+    pub environments: usize,
+    /// distinct frames, environments share the frames they enclose.
+    pub frames: usize,
+}
+
+impl HeapStats {
+    pub fn total_objects(&self) -> usize {
+        self.objects.values().sum()
+    }
+
+    /// as an association list:
+    /// ((objects (cons . 12) (int . 3) ...) (environments . 2) (frames . 2))
+    pub fn to_obj(&self) -> Obj {
+        let count = |name: &str, n: usize| {
+            Obj::cons(Obj::new_symb(name, None), Obj::new_int(n as i64, None))
+        };
+        let objects = self
+            .objects
+            .iter()
+            .map(|(kind, n)| count(kind, *n))
+            .collect();
+        Obj::list_from_vec(
+            vec![
+                Obj::cons(
+                    Obj::new_symb("objects", None),
+                    Obj::list_from_vec(objects, None),
+                ),
+                count("environments", self.environments),
+                count("frames", self.frames),
+            ],
+            None,
+        )
+    }
+}
+
+fn kind(val: &ObjVal) -> &'static str {
+    match val {
+        ObjVal::Symbol(_) => "symbol",
+        ObjVal::Str(_) => "string",
+        ObjVal::Char(_) => "char",
+        ObjVal::Float(_) => "float",
+        ObjVal::Int(_) => "int",
+        ObjVal::Bool(_) => "bool",
+        ObjVal::Closure(_) => "closure",
+        ObjVal::CaseLambda(_) => "case-lambda",
+        ObjVal::Primitive(_) => "primitive",
+        ObjVal::Alias(_) => "alias",
+        ObjVal::Macro(_) => "macro",
+        ObjVal::Cons(_, _) => "cons",
+        ObjVal::Nil => "nil",
+    }
+}
+
+fn obj_addr(obj: &Obj) -> usize {
+    &*obj.val as *const GcCell<ObjVal> as usize
+}
+
+fn frame_addr(env: &Env) -> usize {
+    &*env.frame as *const GcCell<Frame> as usize
+}
+
+// visits every object and frame reachable from an environment once.
+#[derive(Default)]
+struct Walk {
+    objs: HashSet<usize>,
+    frames: HashSet<usize>,
+    stats: HeapStats,
+}
+
+impl Walk {
+    fn env(&mut self, env: &Env, todo: &mut Vec<Obj>) {
+        self.stats.environments += 1;
+        let mut next = Some(env);
+        while let Some(env) = next {
+            if self.frames.insert(frame_addr(env)) {
+                self.stats.frames += 1;
+                todo.extend(env.frame.borrow().symbol_table.values().cloned());
+            }
+            next = env.enclosing.as_deref();
+        }
+    }
+
+    fn closure(&mut self, closure: &Closure, todo: &mut Vec<Obj>) {
+        todo.push(closure.params.clone());
+        todo.push(closure.body.clone());
+        self.env(&closure.env, todo);
+    }
+
+    // queue what an object points to.
+    fn children(&mut self, val: &ObjVal, todo: &mut Vec<Obj>) {
+        match val {
+            ObjVal::Cons(x, y) => {
+                todo.push(x.clone());
+                todo.push(y.clone());
+            }
+            ObjVal::Closure(c) => self.closure(c, todo),
+            ObjVal::CaseLambda(c) => {
+                for clause in c.clauses.iter() {
+                    self.closure(clause, todo);
+                }
+            }
+            ObjVal::Alias(a) => {
+                todo.push(a.base.clone());
+                self.env(&a.env, todo);
+            }
+            ObjVal::Macro(m) => {
+                match &m.transformer {
+                    Transformer::SyntaxRules(rules) => {
+                        for rule in rules.rules.iter() {
+                            todo.push(rule.pattern.clone());
+                            todo.push(rule.template.clone());
+                        }
+                    }
+                    Transformer::Procedure { procedure, .. }
+                    | Transformer::ExplicitRenaming(procedure) => todo.push(procedure.clone()),
+                }
+                self.env(&m.env, todo);
+            }
+            _ => (),
+        }
+    }
+
+    fn from_env(env: &Env) -> Walk {
+        let mut walk = Walk::default();
+        let mut todo = vec![];
+        walk.env(env, &mut todo);
+        walk.run(todo);
+        walk
+    }
+
+    fn from_obj(obj: &Obj) -> Walk {
+        let mut walk = Walk::default();
+        walk.run(vec![obj.clone()]);
+        walk
+    }
+
+    fn run(&mut self, mut todo: Vec<Obj>) {
+        while let Some(obj) = todo.pop() {
+            if !self.objs.insert(obj_addr(&obj)) {
+                continue;
+            }
+            let val = obj.val.borrow();
+            *self.stats.objects.entry(kind(&val)).or_insert(0) += 1;
+            self.children(&val, &mut todo);
+        }
+    }
+}
+
+/// count the objects, environments and frames reachable from env.
+pub fn heap_stats(env: &Env) -> HeapStats {
+    Walk::from_env(env).stats
+}
+
+/// count the objects, environments and frames reachable from obj.
+pub fn heap_stats_of(obj: &Obj) -> HeapStats {
+    Walk::from_obj(obj).stats
+}
+
+/// closures reachable from env that are reachable again from the
+/// environment they captured, the env <-> closure loops reference
+/// counting could never free. they are named by the variable that
+/// binds them in that environment, or <anonymous>.
+pub fn closure_cycles(env: &Env) -> Vec<String> {
+    let mut todo = vec![];
+    let mut walk = Walk::default();
+    walk.env(env, &mut todo);
+    cycles(walk, todo)
+}
+
+/// the closure cycles reachable from obj.
+pub fn closure_cycles_of(obj: &Obj) -> Vec<String> {
+    cycles(Walk::default(), vec![obj.clone()])
+}
+
+fn cycles(mut walk: Walk, mut todo: Vec<Obj>) -> Vec<String> {
+    let mut cycles = vec![];
+    while let Some(obj) = todo.pop() {
+        if !walk.objs.insert(obj_addr(&obj)) {
+            continue;
+        }
+        if let Ok(captured) = obj.environment() {
+            if Walk::from_env(&captured).objs.contains(&obj_addr(&obj)) {
+                cycles.push(binding_name(&captured, &obj));
+            }
+        }
+        walk.children(&obj.val.borrow(), &mut todo);
+    }
+    cycles.sort();
+    cycles
+}
+
+fn binding_name(env: &Env, closure: &Obj) -> String {
+    let mut next = Some(env);
+    while let Some(env) = next {
+        for (sym, val) in env.frame.borrow().symbol_table.iter() {
+            if obj_addr(val) == obj_addr(closure) {
+                return sym.name().to_owned();
+            }
+        }
+        next = env.enclosing.as_deref();
+    }
+    "<anonymous>".to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn eval_in(s: &str, env: &mut Env) -> Obj {
+        let lexer = Lexer::new(s, "test.scm");
+        let mut parser = Parser::new(lexer).unwrap();
+        eval(parser.list().unwrap().to_obj(), env).unwrap()
+    }
+
+    #[test]
+    fn heap_stats_count_reachable() {
+        let mut env = Env::the_global_environment();
+        let before = heap_stats(&env);
+        assert_eq!(before.frames, 1);
+        assert_eq!(before.environments, 1);
+
+        eval_in("(define xs (list 1 2 \"three\"))", &mut env);
+        let after = heap_stats(&env);
+        assert_eq!(before.objects.get("cons"), None);
+        assert_eq!(after.objects["cons"], 3);
+        assert_eq!(after.objects["int"], 2);
+        assert_eq!(after.objects["string"], 1);
+        assert_eq!(after.total_objects() - before.total_objects(), 7);
+
+        // the closure captures the global environment, no new frame.
+        eval_in("(define id (lambda (x) x))", &mut env);
+        let stats = heap_stats(&env);
+        assert_eq!(stats.frames, 1);
+        assert_eq!(stats.environments, 2);
+        assert_eq!(stats.objects["closure"], 1);
+    }
+
+    #[test]
+    fn closure_cycles_found() {
+        let mut env = Env::the_global_environment();
+        assert!(closure_cycles(&env).is_empty());
+        let prog = r#"
+(begin
+  (define counter (lambda ()
+                    (define n 0)
+                    (define get (lambda () n))
+                    get))
+  (define c (counter)))
+"#;
+        eval_in(prog, &mut env);
+        // counter lives in the global frame it captured, get in the
+        // frame of the call that made it.
+        assert_eq!(closure_cycles(&env), vec!["counter", "get"]);
+    }
+}
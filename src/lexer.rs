@@ -1,9 +1,17 @@
+use crate::diagnostic::Diagnostic;
+use crate::source::FileId;
 use crate::types::*;
 use regex::Regex;
+use std::fmt;
 
 impl Token {
-    pub fn new(tok: Tok, start: usize, end: usize) -> Token {
-        Token { tok, start, end }
+    pub fn new(tok: Tok, file: FileId, start: usize, end: usize) -> Token {
+        Token {
+            tok,
+            file,
+            start,
+            end,
+        }
     }
 
     pub fn is_float(&self) -> bool {
@@ -24,6 +32,18 @@ impl Token {
             _ => false,
         }
     }
+    pub fn is_str(&self) -> bool {
+        match self.tok {
+            Tok::Str(_) => true,
+            _ => false,
+        }
+    }
+    pub fn is_char(&self) -> bool {
+        match self.tok {
+            Tok::Char(_) => true,
+            _ => false,
+        }
+    }
     pub fn is_lparen(&self) -> bool {
         match self.tok {
             Tok::LParen => true,
@@ -48,16 +68,108 @@ impl Token {
             _ => false,
         }
     }
+    pub fn is_datum_comment(&self) -> bool {
+        match self.tok {
+            Tok::DatumComment => true,
+            _ => false,
+        }
+    }
+    pub fn is_abbreviation(&self) -> bool {
+        self.tok.abbreviation().is_some()
+    }
+    /// whitespace and comments carry no meaning for the parser.
+    pub fn is_atmosphere(&self) -> bool {
+        match self.tok {
+            Tok::Space | Tok::Comment => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    /// a character that can't start any token.
+    UnexpectedChar { c: char, start: usize, end: usize },
+    /// a string or block comment still open at the end of input.
+    Unterminated {
+        what: &'static str,
+        start: usize,
+        end: usize,
+    },
+    /// digits that run into something other than a delimiter, or
+    /// that don't fit in a number.
+    MalformedNumber {
+        text: String,
+        start: usize,
+        end: usize,
+    },
+    /// a bad string escape or character name.
+    MalformedLiteral {
+        text: String,
+        start: usize,
+        end: usize,
+    },
 }
 
-#[derive(Debug)]
-pub enum LexError {}
+impl LexError {
+    /// the byte offsets of the offending text.
+    pub fn span(&self) -> (usize, usize) {
+        match self {
+            LexError::UnexpectedChar { start, end, .. } => (*start, *end),
+            LexError::Unterminated { start, end, .. } => (*start, *end),
+            LexError::MalformedNumber { start, end, .. } => (*start, *end),
+            LexError::MalformedLiteral { start, end, .. } => (*start, *end),
+        }
+    }
+}
+
+impl LexError {
+    pub fn to_diagnostic(&self, file: FileId) -> Diagnostic {
+        let (start, end) = self.span();
+        let msg = match self {
+            LexError::UnexpectedChar { c, .. } => format!("unexpected character {:?}", c),
+            LexError::Unterminated { what, .. } => format!("unterminated {}", what),
+            LexError::MalformedNumber { text, .. } => format!("malformed number: {}", text),
+            LexError::MalformedLiteral { text, .. } => format!("malformed literal: {}", text),
+        };
+        let diagnostic = Diagnostic::error(&msg).at(Some(Loc::new(file, start, end)));
+        match self {
+            LexError::Unterminated { what, .. } => {
+                diagnostic.with_help(&format!("the {} starts here and never ends", what))
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (start, end) = self.span();
+        match self {
+            LexError::UnexpectedChar { c, .. } => write!(f, "unexpected character {:?}", c)?,
+            LexError::Unterminated { what, .. } => write!(f, "unterminated {}", what)?,
+            LexError::MalformedNumber { text, .. } => write!(f, "malformed number: {}", text)?,
+            LexError::MalformedLiteral { text, .. } => write!(f, "malformed literal: {}", text)?,
+        }
+        write!(f, " @ {}..{}", start, end)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == ')' || c == '"' || c == ';'
+}
+
+// symbols that start like a number, the increment and decrement
+// procedures of older lisps.
+const NUMBER_LIKE_SYMBOLS: [&str; 2] = ["1+", "1-"];
 
 pub struct Lexer {
     idx: usize,
     byte_length: usize,
-    prog: String,
     pub filename: String,
+    pub file: FileId,
 }
 
 impl Lexer {
@@ -65,82 +177,322 @@ impl Lexer {
         Lexer {
             idx: 0,
             byte_length: input.len(),
-            prog: input.to_owned(),
             filename: filename.to_owned(),
+            file: FileId::new(filename, input),
         }
     }
-}
 
-impl Iterator for Lexer {
-    type Item = Result<Token, LexError>; //Spanned<Tok, usize, LexError>;
+    fn token(&self, tok: Tok, start: usize, end: usize) -> Token {
+        Token::new(tok, self.file.clone(), start, end)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.idx >= self.byte_length {
-            return None;
+    // the byte offset where the run of non delimiters starting at
+    // start ends.
+    fn delimited_end(&self, start: usize) -> usize {
+        match self.file.source().text[start..].find(is_delimiter) {
+            Some(i) => start + i,
+            None => self.byte_length,
+        }
+    }
+
+    fn malformed(&self, start: usize, end: usize) -> LexError {
+        LexError::MalformedLiteral {
+            text: self.file.source().text[start..end].to_owned(),
+            start,
+            end,
+        }
+    }
+
+    /// lex a string literal starting at the opening double quote.
+    /// handles the R7RS escapes: \n \t \r \a \b \\ \" \| and \x<hex>;
+    fn string(&mut self) -> Result<Token, LexError> {
+        let start = self.idx;
+        let mut chars = self.file.source().text[start + 1..].char_indices();
+        let mut s = String::new();
+        // a bad escape is reported once the whole string is consumed,
+        // so lexing resumes after the closing quote.
+        let mut bad_escape = None;
+
+        while let Some((i, c)) = chars.next() {
+            let at = start + 1 + i;
+            match c {
+                '"' => {
+                    let end = at + 1;
+                    self.idx = end;
+                    return match bad_escape {
+                        Some(err) => Err(err),
+                        None => Ok(self.token(Tok::Str(s), start, end)),
+                    };
+                }
+                '\\' => {
+                    let e = match chars.next() {
+                        Some((_, e)) => e,
+                        None => break,
+                    };
+                    match e {
+                        'n' => s.push('\n'),
+                        't' => s.push('\t'),
+                        'r' => s.push('\r'),
+                        'a' => s.push('\u{7}'),
+                        'b' => s.push('\u{8}'),
+                        '\\' => s.push('\\'),
+                        '"' => s.push('"'),
+                        '|' => s.push('|'),
+                        'x' | 'X' => {
+                            let mut hex = String::new();
+                            let mut end = None;
+                            for (j, h) in &mut chars {
+                                if h == ';' {
+                                    end = Some(start + 1 + j + 1);
+                                    break;
+                                }
+                                hex.push(h);
+                            }
+                            let end = match end {
+                                Some(end) => end,
+                                None => break,
+                            };
+                            match u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(std::char::from_u32)
+                            {
+                                Some(c) => s.push(c),
+                                None => {
+                                    bad_escape = bad_escape.or(Some(self.malformed(at, end)));
+                                }
+                            }
+                        }
+                        _ => {
+                            let end = at + 1 + e.len_utf8();
+                            bad_escape = bad_escape.or(Some(self.malformed(at, end)));
+                        }
+                    }
+                }
+                _ => s.push(c),
+            }
         }
-        let symbol_pat = Regex::new(r#"[<>&$#/\~\*\-+\p{L}][<>&$#/\~\*\-+\p{L}\\!0-9?]*"#).unwrap();
-        let float_pat = Regex::new(r"[-+]?[0-9]*\.[0-9]+([eE][-+]?[0-9]+)?").unwrap();
+
+        self.idx = self.byte_length;
+        Err(LexError::Unterminated {
+            what: "string",
+            start,
+            end: self.byte_length,
+        })
+    }
+
+    /// a ; comment runs to the end of the line.
+    fn line_comment(&mut self) -> Result<Token, LexError> {
+        let start = self.idx;
+        self.idx = match self.file.source().text[start..].find('\n') {
+            Some(i) => start + i + 1,
+            None => self.byte_length,
+        };
+        Ok(self.token(Tok::Comment, start, self.idx))
+    }
+
+    /// a #| ... |# comment, which may nest.
+    fn block_comment(&mut self) -> Result<Token, LexError> {
+        let start = self.idx;
+        let mut i = start + 2;
+        let mut depth = 1;
+
+        while depth > 0 {
+            let rest = &self.file.source().text[i..];
+            if rest.starts_with("|#") {
+                depth -= 1;
+                i += 2;
+            } else if rest.starts_with("#|") {
+                depth += 1;
+                i += 2;
+            } else if let Some(c) = rest.chars().next() {
+                i += c.len_utf8();
+            } else {
+                self.idx = self.byte_length;
+                return Err(LexError::Unterminated {
+                    what: "block comment",
+                    start,
+                    end: self.byte_length,
+                });
+            }
+        }
+        self.idx = i;
+        Ok(self.token(Tok::Comment, start, self.idx))
+    }
+
+    /// lex a character literal starting at #\, one of
+    /// #\a, #\space (see CHAR_NAMES) or #\x3bb.
+    fn character(&mut self) -> Result<Token, LexError> {
+        let start = self.idx;
+        let body = &self.file.source().text[start + 2..];
+
+        // the first character is always part of the literal, even
+        // if it is a delimiter, as in #\( or #\space.
+        let first = match body.chars().next() {
+            Some(c) => c,
+            None => {
+                self.idx = self.byte_length;
+                return Err(LexError::Unterminated {
+                    what: "character",
+                    start,
+                    end: self.byte_length,
+                });
+            }
+        };
+        let end = self.delimited_end(start + 2 + first.len_utf8());
+        let text = &self.file.source().text[start + 2..end];
+        let malformed = self.malformed(start, end);
+
+        self.idx = end;
+        let c = if text.chars().count() == 1 {
+            first
+        } else if let Some((_, c)) = CHAR_NAMES.iter().find(|(name, _)| *name == text) {
+            *c
+        } else if let Some(hex) = text.strip_prefix('x') {
+            u32::from_str_radix(hex, 16)
+                .ok()
+                .and_then(std::char::from_u32)
+                .ok_or(malformed)?
+        } else {
+            return Err(malformed);
+        };
+        Ok(self.token(Tok::Char(c), start, end))
+    }
+
+    // numbers must be followed by a delimiter, 123abc is not a
+    // number followed by a symbol. 1+ and 1- are symbols.
+    fn number(&mut self, start: usize, end: usize, float: bool) -> Result<Token, LexError> {
+        let next_ok = match self.file.source().text[end..].chars().next() {
+            Some(c) => is_delimiter(c),
+            None => true,
+        };
+        let end = if next_ok {
+            end
+        } else {
+            self.delimited_end(end)
+        };
+        self.idx = end;
+
+        let text = &self.file.source().text[start..end];
+        let tok = if !next_ok {
+            if NUMBER_LIKE_SYMBOLS.contains(&text) {
+                Some(Tok::Symbol(Symb::intern(text)))
+            } else {
+                None
+            }
+        } else if float {
+            text.parse::<f64>().ok().map(Tok::Float)
+        } else {
+            text.parse::<i64>().ok().map(Tok::Int)
+        };
+        match tok {
+            Some(tok) => Ok(self.token(tok, start, end)),
+            None => Err(LexError::MalformedNumber {
+                text: text.to_owned(),
+                start,
+                end,
+            }),
+        }
+    }
+
+    fn lex(&mut self) -> Result<Token, LexError> {
+        let symbol_pat =
+            Regex::new(r#"[<>=&$%:^_!?#/\~\*\-+\p{L}][<>=&$%:^_!?#/\~\*\-+\p{L}\\.@0-9]*"#)
+                .unwrap();
+        let float_pat =
+            Regex::new(r"[-+]?([0-9]*\.[0-9]+([eE][-+]?[0-9]+)?|[0-9]+[eE][-+]?[0-9]+)").unwrap();
         let int_pat = Regex::new(r"[-+]?[0-9]+").unwrap();
         let space_pat = Regex::new(r"[\s\n\t]+").unwrap();
         // symbol_pat.
 
+        if self.file.source().text[self.idx..].starts_with(';') {
+            return self.line_comment();
+        }
+        if self.file.source().text[self.idx..].starts_with("#|") {
+            return self.block_comment();
+        }
+        if self.file.source().text[self.idx..].starts_with("#;") {
+            self.idx += 2;
+            return Ok(self.token(Tok::DatumComment, self.idx - 2, self.idx));
+        }
+        if self.file.source().text[self.idx..].starts_with('"') {
+            return self.string();
+        }
+        if self.file.source().text[self.idx..].starts_with("#\\") {
+            return self.character();
+        }
+        // the ellipsis of syntax-rules, the one symbol starting with a dot.
+        if self.file.source().text[self.idx..].starts_with("...") {
+            self.idx += 3;
+            let tok = Tok::Symbol(Symb::intern("..."));
+            return Ok(self.token(tok, self.idx - 3, self.idx));
+        }
+
         // order matters! must try to parse float before int.
-        match float_pat.find_at(&self.prog, self.idx) {
-            Some(m) => {
-                if m.start() == self.idx {
-                    let tok = Tok::Float(m.as_str().parse::<f64>().unwrap());
-                    self.idx = m.end();
-                    return Some(Ok(Token::new(tok, m.start(), m.end())));
-                }
+        if let Some(m) = float_pat.find_at(&self.file.source().text, self.idx) {
+            if m.start() == self.idx {
+                return self.number(m.start(), m.end(), true);
             }
-            None => {}
         }
 
         // try an int
-        match int_pat.find_at(&self.prog, self.idx) {
-            Some(m) => {
-                if m.start() == self.idx {
-                    let tok = Tok::Int(m.as_str().parse::<i64>().unwrap());
-                    self.idx = m.end();
-                    return Some(Ok(Token::new(tok, m.start(), m.end())));
-                }
+        if let Some(m) = int_pat.find_at(&self.file.source().text, self.idx) {
+            if m.start() == self.idx {
+                return self.number(m.start(), m.end(), false);
             }
-            None => {}
         }
 
-        match space_pat.find_at(&self.prog, self.idx) {
-            Some(m) => {
-                if m.start() == self.idx {
-                    self.idx = m.end();
-                    return Some(Ok(Token::new(Tok::Space, m.start(), m.end())));
-                }
+        if let Some(m) = space_pat.find_at(&self.file.source().text, self.idx) {
+            if m.start() == self.idx {
+                self.idx = m.end();
+                return Ok(self.token(Tok::Space, m.start(), m.end()));
             }
-            None => {}
         }
 
-        match symbol_pat.find_at(&self.prog, self.idx) {
-            Some(m) => {
-                if m.start() == self.idx {
-                    let sym = m.as_str();
-                    let tok = Tok::Symbol(Symb::new(sym, self.filename.clone(), m.start()));
-                    self.idx = m.end();
-                    return Some(Ok(Token::new(tok, m.start(), m.end())));
+        if let Some(m) = symbol_pat.find_at(&self.file.source().text, self.idx) {
+            if m.start() == self.idx {
+                let sym = m.as_str();
+                let tok = Tok::Symbol(Symb::intern(sym));
+                self.idx = m.end();
+                return Ok(self.token(tok, m.start(), m.end()));
+            }
+        }
+
+        let start = self.idx;
+        let c = self.file.source().text[start..].chars().next().unwrap();
+        self.idx += c.len_utf8();
+        match c {
+            ')' => Ok(self.token(Tok::RParen, start, self.idx)),
+            '(' => Ok(self.token(Tok::LParen, start, self.idx)),
+            '.' => Ok(self.token(Tok::Dot, start, self.idx)),
+            '\'' => Ok(self.token(Tok::Quote, start, self.idx)),
+            '`' => Ok(self.token(Tok::Quasiquote, start, self.idx)),
+            ',' => {
+                if self.file.source().text[self.idx..].starts_with('@') {
+                    self.idx += 1;
+                    Ok(self.token(Tok::UnquoteSplicing, start, self.idx))
+                } else {
+                    Ok(self.token(Tok::Unquote, start, self.idx))
                 }
             }
-            None => {}
+            _ => Err(LexError::UnexpectedChar {
+                c,
+                start,
+                end: self.idx,
+            }),
         }
+    }
+}
 
-        let b = self.prog.as_bytes()[self.idx];
-        let c = b as char;
-        self.idx += 1;
-        if c == ')' {
-            return Some(Ok(Token::new(Tok::RParen, self.idx - 1, self.idx)));
-        } else if c == '(' {
-            return Some(Ok(Token::new(Tok::LParen, self.idx - 1, self.idx)));
-        } else if c == '.' {
-            return Some(Ok(Token::new(Tok::Dot, self.idx - 1, self.idx)));
+impl Iterator for Lexer {
+    type Item = Result<Token, LexError>; //Spanned<Tok, usize, LexError>;
+
+    // errors don't end the token stream, lexing picks up again after
+    // the offending text.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.byte_length {
+            None
         } else {
-            return None;
+            Some(self.lex())
         }
     }
 }
@@ -171,10 +523,7 @@ mod tests {
         let mut lexer = Lexer::new("s! asdf asdf asdf", "test.scm");
         if let Some(Ok(tok)) = lexer.next() {
             assert_eq!(tok.start, 0);
-            assert_eq!(
-                tok.tok,
-                Tok::Symbol(Symb::new("s!", "test.scm".to_owned(), 0))
-            );
+            assert_eq!(tok.tok, Tok::Symbol(Symb::intern("s!")));
             assert_eq!(tok.end, 2);
         } else {
             panic!("")
@@ -185,7 +534,7 @@ mod tests {
         let mut lexer = Lexer::new(s, "test.scm");
         if let Some(Ok(tok)) = lexer.next() {
             assert_eq!(tok.start, 0);
-            assert_eq!(tok.tok, Tok::Symbol(Symb::new(s, "test.scm".to_owned(), 0)));
+            assert_eq!(tok.tok, Tok::Symbol(Symb::intern(s)));
             assert_eq!(tok.end, s.len());
         } else {
             panic!("")
@@ -230,10 +579,7 @@ mod tests {
         let mut lexer = Lexer::new("set! asdf asdf asdf", "test.scm");
         if let Some(Ok(tok)) = lexer.next() {
             assert_eq!(tok.start, 0);
-            assert_eq!(
-                tok.tok,
-                Tok::Symbol(Symb::new("set!", "test.scm".to_owned(), 0))
-            );
+            assert_eq!(tok.tok, Tok::Symbol(Symb::intern("set!")));
             assert_eq!(tok.end, 4);
         } else {
             panic!("")
@@ -245,10 +591,7 @@ mod tests {
         let mut lexer = Lexer::new("begin? asdf asdf asdf", "test.scm");
         if let Some(Ok(tok)) = lexer.next() {
             assert_eq!(tok.start, 0);
-            assert_eq!(
-                tok.tok,
-                Tok::Symbol(Symb::new("begin?", "test.scm".to_owned(), 0))
-            );
+            assert_eq!(tok.tok, Tok::Symbol(Symb::intern("begin?")));
             assert_eq!(tok.end, 6);
         } else {
             panic!("")
@@ -312,6 +655,216 @@ mod tests {
         }
     }
 
+    #[test]
+    fn lex_string() {
+        let mut lexer = Lexer::new(r#""hello world" asdf"#, "test.scm");
+        if let Some(Ok(tok)) = lexer.next() {
+            assert_eq!(tok.start, 0);
+            assert_eq!(tok.tok, Tok::Str("hello world".to_owned()));
+            assert_eq!(tok.end, 13);
+        } else {
+            panic!("")
+        }
+    }
+
+    #[test]
+    fn lex_string_escapes() {
+        let mut lexer = Lexer::new(r#""a\n\t\\\"\x41;\x3bb;""#, "test.scm");
+        if let Some(Ok(tok)) = lexer.next() {
+            assert_eq!(tok.tok, Tok::Str("a\n\t\\\"Aλ".to_owned()));
+        } else {
+            panic!("")
+        }
+    }
+
+    #[test]
+    fn lex_string_unicode() {
+        let lexer = Lexer::new(r#"("车" "ε")"#, "test.scm");
+        let toks: Vec<Result<Token, LexError>> = lexer.collect();
+        assert_eq!(5, toks.len());
+    }
+
+    fn lex_char_literal(s: &str, expected: char) {
+        let mut lexer = Lexer::new(s, "test.scm");
+        if let Some(Ok(tok)) = lexer.next() {
+            assert_eq!(tok.start, 0);
+            assert_eq!(tok.tok, Tok::Char(expected));
+        } else {
+            panic!("")
+        }
+    }
+
+    #[test]
+    fn lex_char_literals() {
+        lex_char_literal(r"#\a", 'a');
+        lex_char_literal(r"#\space", ' ');
+        lex_char_literal(r"#\newline", '\n');
+        lex_char_literal(r"#\x3bb", 'λ');
+        lex_char_literal(r"#\x", 'x');
+        lex_char_literal(r"#\车", '车');
+        lex_char_literal(r"#\(", '(');
+        lex_char_literal(r"#\) x", ')');
+    }
+
+    #[test]
+    fn lex_char_in_list() {
+        let lexer = Lexer::new(r"(#\a #\λ)", "test.scm");
+        let toks: Vec<Result<Token, LexError>> = lexer.collect();
+        assert_eq!(5, toks.len());
+    }
+
+    #[test]
+    fn lex_line_comment() {
+        let lexer = Lexer::new("(a ; comment (b)\n c)", "test.scm");
+        let toks: Vec<Token> = lexer.map(|t| t.unwrap()).collect();
+        assert_eq!(toks[3].tok, Tok::Comment);
+        assert_eq!(toks[3].end, 17);
+        let toks: Vec<Token> = toks.into_iter().filter(|t| !t.is_atmosphere()).collect();
+        assert_eq!(4, toks.len());
+    }
+
+    #[test]
+    fn lex_line_comment_at_eof() {
+        let lexer = Lexer::new("a ; no newline", "test.scm");
+        let toks: Vec<Result<Token, LexError>> = lexer.collect();
+        assert_eq!(3, toks.len());
+    }
+
+    #[test]
+    fn lex_ellipsis() {
+        let lexer = Lexer::new("(a ... . b)", "test.scm");
+        let toks: Vec<Tok> = lexer
+            .map(|t| t.unwrap())
+            .filter(|t| !t.is_atmosphere())
+            .map(|t| t.tok)
+            .collect();
+        assert_eq!(toks[2], Tok::Symbol(Symb::intern("...")));
+        assert_eq!(toks[3], Tok::Dot);
+    }
+
+    #[test]
+    fn lex_block_comment_nested() {
+        let lexer = Lexer::new("(a #| x #| y |# (z |# b)", "test.scm");
+        let toks: Vec<Token> = lexer
+            .map(|t| t.unwrap())
+            .filter(|t| !t.is_atmosphere())
+            .collect();
+        assert_eq!(4, toks.len());
+        assert_eq!(toks[2].tok, Tok::Symbol(Symb::intern("b")));
+    }
+
+    #[test]
+    fn lex_datum_comment() {
+        let mut lexer = Lexer::new("#;(a)", "test.scm");
+        if let Some(Ok(tok)) = lexer.next() {
+            assert_eq!(tok.tok, Tok::DatumComment);
+            assert_eq!(tok.end, 2);
+        } else {
+            panic!("")
+        }
+    }
+
+    #[test]
+    fn lex_quote_abbreviations() {
+        let lexer = Lexer::new("'a `(b ,c ,@d)", "test.scm");
+        let toks: Vec<Tok> = lexer
+            .map(|t| t.unwrap())
+            .filter(|t| !t.is_atmosphere())
+            .map(|t| t.tok)
+            .collect();
+        assert_eq!(toks[0], Tok::Quote);
+        assert_eq!(toks[2], Tok::Quasiquote);
+        assert_eq!(toks[5], Tok::Unquote);
+        assert_eq!(toks[7], Tok::UnquoteSplicing);
+        assert_eq!(10, toks.len());
+    }
+
+    fn lex_error(s: &str) -> LexError {
+        let lexer = Lexer::new(s, "test.scm");
+        lexer.filter_map(|t| t.err()).next().unwrap()
+    }
+
+    #[test]
+    fn lex_unexpected_char() {
+        assert_eq!(
+            lex_error("(a [b])"),
+            LexError::UnexpectedChar {
+                c: '[',
+                start: 3,
+                end: 4
+            }
+        );
+        // multibyte characters are reported whole.
+        assert_eq!(lex_error("(a →)").span(), (3, 6));
+    }
+
+    #[test]
+    fn lex_errors_resume() {
+        let lexer = Lexer::new("[a] b", "test.scm");
+        let toks: Vec<Result<Token, LexError>> = lexer.collect();
+        assert_eq!(5, toks.len());
+        assert!(toks[0].is_err() && toks[2].is_err() && toks[4].is_ok());
+    }
+
+    #[test]
+    fn lex_unterminated() {
+        assert_eq!(
+            lex_error(r#"(a "bc"#),
+            LexError::Unterminated {
+                what: "string",
+                start: 3,
+                end: 6
+            }
+        );
+        assert_eq!(lex_error("a #| b #| c |# d").span(), (2, 16));
+        assert_eq!(lex_error("#\\").span(), (0, 2));
+    }
+
+    #[test]
+    fn lex_malformed_number() {
+        assert_eq!(
+            lex_error("(+ 12abc 3)"),
+            LexError::MalformedNumber {
+                text: "12abc".to_owned(),
+                start: 3,
+                end: 8
+            }
+        );
+        assert_eq!(lex_error("1.2.3").span(), (0, 5));
+        assert_eq!(lex_error("99999999999999999999").span(), (0, 20));
+        assert_eq!(lex_error("1+2").span(), (0, 3));
+    }
+
+    #[test]
+    fn lex_number_like_symbols() {
+        let lexer = Lexer::new("(1+ 1-)", "test.scm");
+        let toks: Vec<Tok> = lexer
+            .map(|t| t.unwrap().tok)
+            .filter(|t| *t != Tok::Space)
+            .collect();
+        for (tok, name) in toks[1..3].iter().zip(["1+", "1-"].iter()) {
+            assert_eq!(*tok, Tok::Symbol(Symb::intern(name)));
+        }
+    }
+
+    #[test]
+    fn lex_float_exponent() {
+        let mut lexer = Lexer::new("1e3)", "test.scm");
+        assert_eq!(lexer.next().unwrap().unwrap().tok, Tok::Float(1000.0));
+    }
+
+    #[test]
+    fn lex_malformed_literals() {
+        assert_eq!(lex_error(r#""a\qb""#).span(), (2, 4));
+        assert_eq!(lex_error(r#""\x110000;""#).span(), (1, 10));
+        assert_eq!(lex_error("#\\bogus x").span(), (0, 7));
+
+        // lexing resumes after the string with the bad escape.
+        let lexer = Lexer::new(r#""a\qb" c"#, "test.scm");
+        let toks: Vec<Result<Token, LexError>> = lexer.collect();
+        assert_eq!(3, toks.len());
+    }
+
     #[test]
     fn float_experiment() {
         let pattern = Regex::new(r"[-+]?[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)?").unwrap();
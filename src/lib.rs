@@ -5,11 +5,17 @@
 //#![allow(unused_variables)]
 
 pub mod ast;
+pub mod diagnostic;
 pub mod env;
+pub mod error;
 pub mod eval;
 pub mod frame;
+pub mod heap;
 pub mod lexer;
 pub mod obj;
 pub mod parser;
+pub mod source;
+pub mod symbol;
+pub mod syntax;
 pub mod token;
 pub mod types;
@@ -1,22 +1,35 @@
 use std::fs;
 
+use lazarus::diagnostic::Diagnostic;
 use lazarus::eval::eval;
 use lazarus::lexer::Lexer;
 use lazarus::parser::Parser;
 use lazarus::types::*;
 use std::env;
+use std::process;
 
-fn get_parser(s: &str, filename: &str) -> Parser {
+fn get_parser(s: &str, filename: &str) -> Result<Parser, Diagnostic> {
     let lexer = Lexer::new(s, filename);
-    Parser::new(lexer)
+    let file = lexer.file.clone();
+    Parser::new(lexer).map_err(|err| err.to_diagnostic(file))
 }
 
-fn eval_str(s: &str, filename: &str) -> EvalResult<Obj> {
+// evaluate the top level forms one after another in one global
+// environment, the result is that of the last form.
+fn eval_str(s: &str, filename: &str) -> Result<Obj, Diagnostic> {
     let mut env = Env::the_global_environment();
-    let mut parser = get_parser(s, filename);
-    let parse_results = parser.list().unwrap();
-    let obj = parse_results.to_obj();
-    eval(obj, &mut env)
+    let mut parser = get_parser(s, filename)?;
+    let forms = parser.program().map_err(|err| err.to_diagnostic())?;
+    let mut result = Obj::nil(None);
+    for form in forms {
+        let loc = form.loc();
+        result = eval(form.to_obj(), &mut env).map_err(|err| {
+            err.or_at(&loc)
+                .to_diagnostic()
+                .with_note("the error happened while evaluating this top level form")
+        })?;
+    }
+    Ok(result)
 }
 
 fn main() {    
@@ -24,5 +37,11 @@ fn main() {
     let filename = &args[1];
     let prog = fs::read_to_string(&filename.as_str());
     println!("file: {:?}", filename);
-    println!("{:?}", eval_str(&prog.unwrap(), &filename.as_str()));
+    match eval_str(&prog.unwrap(), &filename.as_str()) {
+        Ok(result) => println!("{:?}", result),
+        Err(diagnostic) => {
+            eprint!("{}", diagnostic);
+            process::exit(1);
+        }
+    }
 }
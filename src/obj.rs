@@ -1,11 +1,10 @@
 use crate::types::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::collections::HashSet;
 
 impl Obj {
     pub fn new(val: ObjVal, loc: Option<Loc>) -> Obj {
         Obj {
-            val: Rc::new(RefCell::new(val)),
+            val: mutcell(val),
             loc,
         }
     }
@@ -18,12 +17,25 @@ impl Obj {
         Obj::new(ObjVal::Float(num), loc)
     }
 
+    pub fn new_str(s: String, loc: Option<Loc>) -> Obj {
+        Obj::new(ObjVal::Str(s), loc)
+    }
+
+    pub fn new_char(c: char, loc: Option<Loc>) -> Obj {
+        Obj::new(ObjVal::Char(c), loc)
+    }
+
     pub fn nil(loc: Option<Loc>) -> Obj {
         Obj::new(ObjVal::Nil, loc)
     }
 
     pub fn list_from_vec(xs: Vec<Obj>, loc: Option<Loc>) -> Obj {
-        let mut list = Obj::empty_list(loc.clone());
+        Obj::dotted_list_from_vec(xs, Obj::empty_list(loc.clone()), loc)
+    }
+
+    /// build (x₁ x₂ … xₙ . tail), which is a proper list when tail is nil.
+    pub fn dotted_list_from_vec(xs: Vec<Obj>, tail: Obj, loc: Option<Loc>) -> Obj {
+        let mut list = tail;
         for x in xs.iter().rev() {
             list = Obj::new(ObjVal::Cons(x.clone(), list), loc.clone());
         }
@@ -38,20 +50,36 @@ impl Obj {
         Obj::new(ObjVal::Cons(x.clone(), y.clone()), x.loc.clone())
     }
 
-    pub fn new_symb(name: String, loc: Option<Loc>) -> Obj {
-        Obj::new(ObjVal::Symbol(name), loc)
+    pub fn new_symb(name: &str, loc: Option<Loc>) -> Obj {
+        Obj::from_symb(Symb::intern(name), loc)
+    }
+
+    pub fn from_symb(symb: Symb, loc: Option<Loc>) -> Obj {
+        Obj::new(ObjVal::Symbol(symb), loc)
     }
 
     pub fn new_bool(b: bool, loc: Option<Loc>) -> Obj {
         Obj::new(ObjVal::Bool(b), loc)
     }
 
-    pub fn from_env(env: Env, loc: Option<Loc>) -> Obj {
-        Obj::new(ObjVal::Env(env), loc)
+    /// a closure over env, named later by define.
+    pub fn new_closure(params: Obj, body: Obj, env: Env, loc: Option<Loc>) -> EvalResult<Obj> {
+        let closure = Closure::new(params, body, env, loc.clone())?;
+        Ok(Obj::new(ObjVal::Closure(closure), loc))
+    }
+
+    pub fn new_case_lambda(clauses: Vec<Closure>, loc: Option<Loc>) -> Obj {
+        let case_lambda = CaseLambda {
+            name: None,
+            clauses,
+            loc: loc.clone(),
+        };
+        Obj::new(ObjVal::CaseLambda(case_lambda), loc)
     }
 
-    pub fn new_primitive_func(f: fn(Obj) -> EvalResult<Obj>, loc: Option<Loc>) -> Obj {
-        Obj::new(ObjVal::PrimFunc(f), loc)
+    pub fn new_primitive(name: &str, func: fn(Obj) -> EvalResult<Obj>, arity: Arity) -> Obj {
+        let name = Symb::intern(name);
+        Obj::new(ObjVal::Primitive(Primitive { name, arity, func }), None)
     }
 
     // the Symb type exists and Obj::Symbol exists.
@@ -62,11 +90,47 @@ impl Obj {
     // for now, just get this working.
     // it will become evident what to do as the system grows.
     pub fn to_symb(&self) -> EvalResult<Symb> {
-        if let ObjVal::Symbol(sym) = &*self.val.borrow() {
-            Ok(Symb::new(&sym.clone(), "".to_owned(), 0))
+        match &*self.val.borrow() {
+            ObjVal::Symbol(sym) => Ok(*sym),
+            // binding an alias binds its fresh symbol.
+            ObjVal::Alias(alias) => Ok(alias.symb),
+            _ => Err(LazarusError::wrong_type("symbol", self)),
+        }
+    }
+
+    /// the symbol an identifier was written as, before any macro
+    /// renamed it.
+    pub fn root_symb(&self) -> Option<Symb> {
+        match &*self.val.borrow() {
+            ObjVal::Symbol(sym) => Some(*sym),
+            ObjVal::Alias(alias) => alias.base.root_symb(),
+            _ => None,
+        }
+    }
+
+    pub fn is_alias(&self) -> bool {
+        if let ObjVal::Alias(..) = *self.val.borrow() {
+            true
         } else {
-            //self.pretty();
-            Err(format!("Not a symbol!: {:?}", self))
+            false
+        }
+    }
+
+    /// the datum with every alias in it replaced by its root symbol,
+    /// what quote gives for a template of a macro. the object itself
+    /// when there are no aliases in it.
+    pub fn strip_syntax(&self) -> Obj {
+        match &*self.val.borrow() {
+            ObjVal::Alias(_) => Obj::from_symb(self.root_symb().unwrap(), self.loc.clone()),
+            ObjVal::Cons(x, y) => {
+                let (sx, sy) = (x.strip_syntax(), y.strip_syntax());
+                if sx.is_same(x) && sy.is_same(y) {
+                    self.clone()
+                } else {
+                    Obj::new(ObjVal::Cons(sx, sy), self.loc.clone())
+                }
+            }
+            _ => self.clone(),
         }
     }
 
@@ -74,7 +138,31 @@ impl Obj {
         if let ObjVal::Int(n) = *self.val.borrow() {
             Ok(n)
         } else {
-            Err(format!("looking for an integer value, found: {:?}", self))
+            Err(LazarusError::wrong_type("integer", self))
+        }
+    }
+
+    pub fn str_val(&self) -> EvalResult<String> {
+        if let ObjVal::Str(s) = &*self.val.borrow() {
+            Ok(s.clone())
+        } else {
+            Err(LazarusError::wrong_type("string", self))
+        }
+    }
+
+    pub fn char_val(&self) -> EvalResult<char> {
+        if let ObjVal::Char(c) = *self.val.borrow() {
+            Ok(c)
+        } else {
+            Err(LazarusError::wrong_type("char", self))
+        }
+    }
+
+    pub fn symbol_name(&self) -> EvalResult<String> {
+        if let ObjVal::Symbol(s) = *self.val.borrow() {
+            Ok(s.to_string())
+        } else {
+            Err(LazarusError::wrong_type("symbol", self))
         }
     }
 
@@ -98,7 +186,7 @@ impl Obj {
         match *self.val.borrow() {
             ObjVal::Float(n) => Ok(n),
             ObjVal::Int(n) => Ok(n as f64),
-            _ => Err("may only covert float or int to float".to_string()),
+            _ => Err(LazarusError::wrong_type("number", self)),
         }
     }
 
@@ -110,8 +198,24 @@ impl Obj {
         }
     }
 
+    /// a symbol or an alias of one, any identifier.
     pub fn is_symbol(&self) -> bool {
-        if let ObjVal::Symbol(..) = *self.val.borrow() {
+        match *self.val.borrow() {
+            ObjVal::Symbol(..) | ObjVal::Alias(..) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_str(&self) -> bool {
+        if let ObjVal::Str(..) = *self.val.borrow() {
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    pub fn is_char(&self) -> bool {
+        if let ObjVal::Char(..) = *self.val.borrow() {
             return true;
         } else {
             return false;
@@ -134,12 +238,8 @@ impl Obj {
         }
     }
 
-    pub fn is_func(&self) -> bool {
-        if let ObjVal::PrimFunc(..) = *self.val.borrow() {
-            true
-        } else {
-            false
-        }
+    pub fn is_procedure(&self) -> bool {
+        self.is_primitive_procedure() || self.is_compound_procedure()
     }
 
     pub fn is_cons(&self) -> bool {
@@ -155,20 +255,22 @@ impl Obj {
     }
 
     pub fn is_self_evaluating(&self) -> bool {
-        return self.is_float() || self.is_int();
+        return self.is_float() || self.is_int() || self.is_str() || self.is_char();
     }
 
     pub fn describe_type(&self) -> &str {
-        if self.is_int() {
-            return "int";
-        } else if self.is_float() {
-            return "float";
-        } else if self.is_list() {
-            return "list";
-        } else if self.is_bool() {
-            return "bool";
-        } else {
-            return "symbol";
+        match &*self.val.borrow() {
+            ObjVal::Symbol(_) => "symbol",
+            ObjVal::Str(_) => "string",
+            ObjVal::Char(_) => "char",
+            ObjVal::Float(_) => "float",
+            ObjVal::Int(_) => "int",
+            ObjVal::Bool(_) => "bool",
+            ObjVal::Closure(_) | ObjVal::CaseLambda(_) | ObjVal::Primitive(_) => "procedure",
+            ObjVal::Alias(_) => "identifier",
+            ObjVal::Macro(_) => "macro",
+            ObjVal::Cons(_, _) => "pair",
+            ObjVal::Nil => "empty list",
         }
     }
 
@@ -180,21 +282,31 @@ impl Obj {
         match &*self.val.borrow() {
             ObjVal::Nil => Ok(0),
             ObjVal::Cons(_, y) => Ok(y.list_length()? + 1),
-            _ => Err("list_length was not passed a list".to_owned()),
+            _ => Err(LazarusError::wrong_type("list", self)),
         }
     }
 
+    pub fn list_to_vec(&self) -> EvalResult<Vec<Obj>> {
+        let mut xs = vec![];
+        let mut list = self.clone();
+        while !list.is_null()? {
+            xs.push(list.car()?);
+            list = list.cdr()?;
+        }
+        Ok(xs)
+    }
+
     pub fn car(&self) -> EvalResult<Obj> {
         match &*self.val.borrow() {
             ObjVal::Cons(x, _) => Ok(x.clone()),
-            _ => Err("car was not passed a cons cell".to_owned()),
+            _ => Err(LazarusError::wrong_type("pair", self)),
         }
     }
 
     pub fn cdr(&self) -> EvalResult<Obj> {
         match &*self.val.borrow() {
             ObjVal::Cons(_, y) => Ok(y.clone()),
-            _ => Err("object passed to cdr is not the correct type.".to_owned()),
+            _ => Err(LazarusError::wrong_type("pair", self)),
         }
     }
 
@@ -223,38 +335,56 @@ impl Obj {
     }
 
     pub fn string_matches(&self, s: &str) -> bool {
-        if let ObjVal::Symbol(sym) = &*self.val.borrow() {
-            s == sym
+        if let ObjVal::Symbol(sym) = *self.val.borrow() {
+            s == sym.name()
         } else {
             false
         }
     }
 
-    pub fn is_tagged_list(&self, tag: &str) -> bool {
-        match self.car() {
-            Ok(obj) => obj.string_matches(tag),
-            Err(..) => false,
+    /// symb, or an alias a macro made of it. that is how keywords a
+    /// macro puts into its expansion are recognized.
+    pub fn is_symb(&self, symb: Symb) -> bool {
+        self.root_symb() == Some(symb)
+    }
+
+    pub fn is_tagged_list(&self, tag: Symb) -> bool {
+        match &*self.val.borrow() {
+            ObjVal::Cons(head, _) => head.is_symb(tag),
+            _ => false,
         }
     }
 
     pub fn is_quoted(&self) -> bool {
-        self.is_tagged_list("quote")
+        self.is_tagged_list(Symb::QUOTE)
     }
 
     pub fn text_of_quotation(&self) -> EvalResult<Obj> {
         self.cadr()
     }
 
+    pub fn is_quasiquoted(&self) -> bool {
+        self.is_tagged_list(Symb::QUASIQUOTE)
+    }
+
+    pub fn is_unquoted(&self) -> bool {
+        self.is_tagged_list(Symb::UNQUOTE)
+    }
+
+    pub fn is_unquote_splicing(&self) -> bool {
+        self.is_tagged_list(Symb::UNQUOTE_SPLICING)
+    }
+
     pub fn is_assignment(&self) -> bool {
-        self.is_tagged_list("set!")
+        self.is_tagged_list(Symb::SET)
     }
 
     pub fn is_definition(&self) -> bool {
-        self.is_tagged_list("define")
+        self.is_tagged_list(Symb::DEFINE)
     }
 
-    pub fn assignment_variable(&self) -> EvalResult<Symb> {
-        self.cadr()?.to_symb()
+    pub fn assignment_variable(&self) -> EvalResult<Obj> {
+        self.cadr()
     }
 
     pub fn assignment_value(&self) -> EvalResult<Obj> {
@@ -262,7 +392,7 @@ impl Obj {
     }
 
     pub fn is_if(&self) -> bool {
-        self.is_tagged_list("if")
+        self.is_tagged_list(Symb::IF)
     }
     pub fn if_predicate(&self) -> EvalResult<Obj> {
         self.cadr()
@@ -292,25 +422,121 @@ impl Obj {
         if self.cadr()?.is_symbol() {
             self.cadr()
         } else {
-            self.caddr()
+            self.cadr()?.car()
         }
     }
 
-    fn make_lambda(params: Obj, body: Obj) -> Obj {
-        let lambda = Obj::new_symb("lambda".to_string(), None);
+    fn make_lambda(params: Obj, body: Obj, loc: Option<Loc>) -> Obj {
+        let lambda = Obj::from_symb(Symb::LAMBDA, loc);
         Obj::cons(lambda, Obj::cons(params, body))
     }
 
+    // let -------------------------------------------------------------------
+    // the let forms are derived expressions, they are rewritten into
+    // lambdas and internal defines and the rewrite is evaluated.
+
+    pub fn is_let(&self) -> bool {
+        self.is_tagged_list(Symb::LET)
+    }
+
+    pub fn is_let_star(&self) -> bool {
+        self.is_tagged_list(Symb::LET_STAR)
+    }
+
+    /// letrec and letrec* are the same here, the inits are evaluated
+    /// left to right and each can see the variables bound before it.
+    pub fn is_letrec(&self) -> bool {
+        self.is_tagged_list(Symb::LETREC) || self.is_tagged_list(Symb::LETREC_STAR)
+    }
+
+    // ((⟨var⟩ ⟨init⟩) …) as the vars and the inits.
+    fn let_bindings(bindings: &Obj) -> EvalResult<(Vec<Obj>, Vec<Obj>)> {
+        let mut vars = vec![];
+        let mut inits = vec![];
+        for binding in bindings.list_to_vec()? {
+            vars.push(binding.car()?);
+            inits.push(binding.cadr()?);
+        }
+        Ok((vars, inits))
+    }
+
+    /// (let ((⟨var⟩ ⟨init⟩) …) ⟨body⟩) => ((lambda (⟨var⟩ …) ⟨body⟩) ⟨init⟩ …)
+    ///
+    /// named let, (let ⟨name⟩ ((⟨var⟩ ⟨init⟩) …) ⟨body⟩), binds name to
+    /// the lambda in a letrec, so calling it in tail position loops.
+    pub fn let_to_combination(&self) -> EvalResult<Obj> {
+        let loc = self.loc.clone();
+        if self.cadr()?.is_symbol() {
+            let name = self.cadr()?;
+            let (vars, inits) = Obj::let_bindings(&self.caddr()?)?;
+            let vars = Obj::list_from_vec(vars, loc.clone());
+            let proc = Obj::make_lambda(vars, self.cdddr()?, loc.clone());
+            let binding = Obj::list_from_vec(vec![name.clone(), proc], loc.clone());
+            let letrec = Obj::list_from_vec(
+                vec![
+                    Obj::from_symb(Symb::LETREC, loc.clone()),
+                    Obj::list_from_vec(vec![binding], loc.clone()),
+                    name,
+                ],
+                loc.clone(),
+            );
+            Ok(Obj::cons(letrec, Obj::list_from_vec(inits, loc)))
+        } else {
+            let (vars, inits) = Obj::let_bindings(&self.cadr()?)?;
+            let vars = Obj::list_from_vec(vars, loc.clone());
+            let proc = Obj::make_lambda(vars, self.cddr()?, loc.clone());
+            Ok(Obj::cons(proc, Obj::list_from_vec(inits, loc)))
+        }
+    }
+
+    /// (let* (⟨binding₁⟩ ⟨binding₂⟩ …) ⟨body⟩) => (let (⟨binding₁⟩) (let* (⟨binding₂⟩ …) ⟨body⟩))
+    pub fn let_star_to_nested_lets(&self) -> EvalResult<Obj> {
+        let loc = self.loc.clone();
+        let bindings = self.cadr()?;
+        let body = self.cddr()?;
+        let tagged = |tag: Symb, rest: Obj| Obj::cons(Obj::from_symb(tag, loc.clone()), rest);
+        if bindings.is_null()? || bindings.cdr()?.is_null()? {
+            return Ok(tagged(Symb::LET, Obj::cons(bindings, body)));
+        }
+        let inner = tagged(Symb::LET_STAR, Obj::cons(bindings.cdr()?, body));
+        let first = Obj::list_from_vec(vec![bindings.car()?], loc.clone());
+        Ok(tagged(
+            Symb::LET,
+            Obj::list_from_vec(vec![first, inner], loc.clone()),
+        ))
+    }
+
+    /// (letrec ((⟨var⟩ ⟨init⟩) …) ⟨body⟩) => ((lambda () (define ⟨var⟩ ⟨init⟩) … ⟨body⟩))
+    pub fn letrec_to_defines(&self) -> EvalResult<Obj> {
+        let loc = self.loc.clone();
+        let (vars, inits) = Obj::let_bindings(&self.cadr()?)?;
+        let defines = vars
+            .into_iter()
+            .zip(inits)
+            .map(|(var, init)| {
+                let define = Obj::from_symb(Symb::DEFINE, loc.clone());
+                Obj::list_from_vec(vec![define, var, init], loc.clone())
+            })
+            .collect();
+        let body = Obj::dotted_list_from_vec(defines, self.cddr()?, loc.clone());
+        let proc = Obj::make_lambda(Obj::empty_list(loc.clone()), body, loc.clone());
+        Ok(Obj::list_from_vec(vec![proc], loc))
+    }
+
     pub fn definition_value(&self) -> EvalResult<Obj> {
         if self.cadr()?.is_symbol() {
             self.caddr()
         } else {
-            Ok(Obj::make_lambda(self.cdadr()?, self.cddr()?))
+            Ok(Obj::make_lambda(
+                self.cdadr()?,
+                self.cddr()?,
+                self.loc.clone(),
+            ))
         }
     }
 
     pub fn is_lambda(&self) -> bool {
-        self.is_tagged_list("lambda")
+        self.is_tagged_list(Symb::LAMBDA)
     }
 
     pub fn lambda_parameters(&self) -> EvalResult<Obj> {
@@ -321,8 +547,78 @@ impl Obj {
         self.cddr()
     }
 
+    /// (case-lambda (⟨formals⟩ ⟨body⟩) …)
+    pub fn is_case_lambda(&self) -> bool {
+        self.is_tagged_list(Symb::CASE_LAMBDA)
+    }
+
+    pub fn case_lambda_clauses(&self) -> EvalResult<Obj> {
+        self.cdr()
+    }
+
+    // do ----------------------------------------------------------------------
+
+    /// (do ((⟨variable⟩ ⟨init⟩ ⟨step⟩) …) (⟨test⟩ ⟨expression⟩ …) ⟨command⟩ …),
+    /// the step of a variable can be left out.
+    pub fn is_do(&self) -> bool {
+        self.is_tagged_list(Symb::DO)
+    }
+
+    pub fn do_bindings(&self) -> EvalResult<Obj> {
+        self.cadr()
+    }
+
+    pub fn do_test_clause(&self) -> EvalResult<Obj> {
+        self.caddr()
+    }
+
+    pub fn do_commands(&self) -> EvalResult<Obj> {
+        self.cdddr()
+    }
+
+    // macros ------------------------------------------------------------------
+
+    /// (define-syntax ⟨keyword⟩ ⟨transformer⟩)
+    pub fn is_define_syntax(&self) -> bool {
+        self.is_tagged_list(Symb::DEFINE_SYNTAX)
+    }
+
+    /// (define-macro (⟨keyword⟩ . ⟨params⟩) ⟨body⟩)
+    pub fn is_define_macro(&self) -> bool {
+        self.is_tagged_list(Symb::DEFINE_MACRO)
+    }
+
+    /// (let-syntax ((⟨keyword⟩ ⟨transformer⟩) …) ⟨body⟩), and the same
+    /// for letrec-syntax.
+    pub fn is_let_syntax(&self) -> bool {
+        self.is_tagged_list(Symb::LET_SYNTAX)
+    }
+
+    pub fn is_letrec_syntax(&self) -> bool {
+        self.is_tagged_list(Symb::LETREC_SYNTAX)
+    }
+
+    pub fn is_syntax_rules(&self) -> bool {
+        self.is_tagged_list(Symb::SYNTAX_RULES)
+    }
+
+    pub fn is_macro(&self) -> bool {
+        if let ObjVal::Macro(..) = *self.val.borrow() {
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn to_macro(&self) -> EvalResult<Macro> {
+        match &*self.val.borrow() {
+            ObjVal::Macro(m) => Ok(m.clone()),
+            _ => Err(LazarusError::wrong_type("macro", self)),
+        }
+    }
+
     pub fn is_begin(&self) -> bool {
-        self.is_tagged_list("begin")
+        self.is_tagged_list(Symb::BEGIN)
     }
 
     pub fn begin_actions(&self) -> EvalResult<Obj> {
@@ -341,34 +637,77 @@ impl Obj {
     }
 
     // cond ------------------------------------------------------------------
-    // skip cond for now.
-    // pub fn is_cond(&self) -> bool {
-    //     self.is_tagged_list("cond")
-    // }
+    // cond, case, when, unless, and and or are evaluated natively, see
+    // eval.rs, so their last expressions run in tail position.
+
+    pub fn is_cond(&self) -> bool {
+        self.is_tagged_list(Symb::COND)
+    }
+
+    pub fn cond_clauses(&self) -> EvalResult<Obj> {
+        self.cdr()
+    }
+
+    pub fn cond_predicate(&self) -> EvalResult<Obj> {
+        self.car()
+    }
+
+    pub fn is_cond_else_clause(&self) -> EvalResult<bool> {
+        Ok(self.cond_predicate()?.is_symb(Symb::ELSE))
+    }
+
+    pub fn cond_actions(&self) -> EvalResult<Obj> {
+        self.cdr()
+    }
+
+    /// (⟨test⟩ => ⟨receiver⟩), the receiver is called with the value of
+    /// the test. also used for the clauses of case.
+    pub fn is_cond_arrow_clause(&self) -> EvalResult<bool> {
+        let actions = self.cond_actions()?;
+        Ok(actions.is_cons() && actions.car()?.is_symb(Symb::ARROW))
+    }
 
-    // pub fn cond_clauses(&self) -> EvalResult<Obj> {
-    //     self.cdr()
-    // }
+    pub fn cond_receiver(&self) -> EvalResult<Obj> {
+        self.cond_actions()?.cadr()
+    }
 
-    // pub fn cond_predicate(&self) -> EvalResult<Obj> {
-    //     self.car()
-    // }
+    /// (case ⟨key⟩ ((⟨datum⟩ …) ⟨expression⟩ …) … (else ⟨expression⟩ …))
+    pub fn is_case(&self) -> bool {
+        self.is_tagged_list(Symb::CASE)
+    }
+
+    pub fn case_key(&self) -> EvalResult<Obj> {
+        self.cadr()
+    }
 
-    // pub fn is_cond_else_clause(&self) -> bool {
-    //     self.cond_predicate().string_matches("else")
-    // }
+    pub fn case_clauses(&self) -> EvalResult<Obj> {
+        self.cddr()
+    }
 
-    // pub fn cond_actions(&self) -> EvalResult<Obj> {
-    //     unimplemented()
-    // }
+    /// (when ⟨test⟩ ⟨expression⟩ …) and (unless ⟨test⟩ ⟨expression⟩ …)
+    pub fn is_when(&self) -> bool {
+        self.is_tagged_list(Symb::WHEN)
+    }
 
-    // pub fn cond_to_if(&self) -> EvalResult<Obj> {
-    //     unimplemented()
-    // }
+    pub fn is_unless(&self) -> bool {
+        self.is_tagged_list(Symb::UNLESS)
+    }
 
-    // pub fn expand_clauses(&self) -> EvalResult<Obj> {
-    //     unimplemented()
-    // }
+    pub fn when_test(&self) -> EvalResult<Obj> {
+        self.cadr()
+    }
+
+    pub fn when_actions(&self) -> EvalResult<Obj> {
+        self.cddr()
+    }
+
+    pub fn is_and(&self) -> bool {
+        self.is_tagged_list(Symb::AND)
+    }
+
+    pub fn is_or(&self) -> bool {
+        self.is_tagged_list(Symb::OR)
+    }
 
     // apply helpers -------------------------------------------------------------------------------
     pub fn is_application(&self) -> bool {
@@ -393,58 +732,167 @@ impl Obj {
     }
 
     pub fn is_primitive_procedure(&self) -> bool {
-        self.is_tagged_list("primitive")
+        if let ObjVal::Primitive(..) = *self.val.borrow() {
+            true
+        } else {
+            false
+        }
     }
 
     pub fn is_compound_procedure(&self) -> bool {
-        self.is_tagged_list("procedure")
+        match *self.val.borrow() {
+            ObjVal::Closure(..) | ObjVal::CaseLambda(..) => true,
+            _ => false,
+        }
     }
 
-    pub fn is_primitive_implementation(&self) -> EvalResult<Obj> {
-        self.cadr()
+    /// the primitive to apply to n arguments.
+    pub fn primitive_for(&self, n: usize) -> EvalResult<Primitive> {
+        match &*self.val.borrow() {
+            ObjVal::Primitive(p) if p.arity.accepts(n) => Ok(p.clone()),
+            ObjVal::Primitive(p) => {
+                Err(LazarusError::arity(p.name.name(), &p.arity.to_string(), n))
+            }
+            _ => Err(LazarusError::wrong_type("primitive procedure", self)),
+        }
     }
 
-    pub fn primitive_apply_to(&self, args: Obj) -> EvalResult<Obj> {
-        // println!("self: {:?} ", self);
-        // println!("args: {:?} ", args);
-        // println!("prim: {:?} ", self.cadr()?);
-
-        if let ObjVal::PrimFunc(f) = &*self.cadr()?.val.borrow() {
-            f(args)
-        } else {
-            Err("Tried to apply something other \
-                 than primitive procedure"
-                .to_string())
+    /// the closure to apply to n arguments, for a case-lambda the first
+    /// clause that accepts them.
+    pub fn closure_for(&self, n: usize) -> EvalResult<Closure> {
+        match &*self.val.borrow() {
+            ObjVal::Closure(c) if c.arity.accepts(n) => Ok(c.clone()),
+            ObjVal::Closure(c) => {
+                let name = c.name.map_or("lambda", |name| name.name());
+                Err(LazarusError::arity(name, &c.arity.to_string(), n))
+            }
+            ObjVal::CaseLambda(c) => match c.clauses.iter().find(|c| c.arity.accepts(n)) {
+                Some(clause) => {
+                    let mut clause = clause.clone();
+                    clause.name = c.name;
+                    Ok(clause)
+                }
+                None => {
+                    let name = c.name.map_or("case-lambda", |name| name.name());
+                    let expected: Vec<String> =
+                        c.clauses.iter().map(|c| c.arity.to_string()).collect();
+                    Err(LazarusError::arity(name, &expected.join(" or "), n))
+                }
+            },
+            _ => Err(LazarusError::wrong_type("compound procedure", self)),
         }
     }
 
     pub fn body(&self) -> EvalResult<Obj> {
-        self.caddr()
+        match &*self.val.borrow() {
+            ObjVal::Closure(c) => Ok(c.body.clone()),
+            _ => Err(LazarusError::wrong_type("compound procedure", self)),
+        }
     }
 
     pub fn parameters(&self) -> EvalResult<Obj> {
-        self.cadr()
+        match &*self.val.borrow() {
+            ObjVal::Closure(c) => Ok(c.params.clone()),
+            _ => Err(LazarusError::wrong_type("compound procedure", self)),
+        }
     }
 
+    /// the environment a procedure captured, the clauses of a
+    /// case-lambda all capture the same one.
     pub fn environment(&self) -> EvalResult<Env> {
-        if !self.is_compound_procedure() {
-            return Err(format!(
-                "Can only call Obj::environment \
-                 on compound procedure, got: {}",
-                self.describe_type()
-            ));
-        }
-        if let ObjVal::Env(env) = &*self.cadddr()?.val.borrow() {
-            // cloning this.. possible bug, need to see if the values
-            // are going to mutate inside this things as expected by
-            // apply and eval.
-            Ok(env.clone())
-        } else {
-            panic!(
-                "interpeter bug, was expecting env, got: {:?}",
-                self.describe_type()
-            );
+        match &*self.val.borrow() {
+            ObjVal::Closure(c) => Ok(c.env.clone()),
+            ObjVal::CaseLambda(c) if !c.clauses.is_empty() => Ok(c.clauses[0].env.clone()),
+            _ => Err(LazarusError::wrong_type("compound procedure", self)),
+        }
+    }
+
+    /// the name of a procedure, None for anonymous closures.
+    pub fn procedure_name(&self) -> Option<Symb> {
+        match &*self.val.borrow() {
+            ObjVal::Closure(c) => c.name,
+            ObjVal::CaseLambda(c) => c.name,
+            ObjVal::Primitive(p) => Some(p.name),
+            _ => None,
+        }
+    }
+
+    /// name an anonymous closure, a closure keeps the first name it is
+    /// defined with.
+    pub fn name_procedure(&self, name: Symb) {
+        match &mut *self.val.borrow_mut() {
+            ObjVal::Closure(c) => {
+                c.name.get_or_insert(name);
+            }
+            ObjVal::CaseLambda(c) => {
+                c.name.get_or_insert(name);
+            }
+            _ => (),
+        }
+    }
+
+    // equivalence -------------------------------------------------------------
+
+    /// the same heap cell.
+    pub fn is_same(&self, other: &Obj) -> bool {
+        std::ptr::eq(&*self.val, &*other.val)
+    }
+
+    /// eq?, the same object. symbols, booleans, chars, integers and the
+    /// empty list are immediate values and compared by value.
+    pub fn is_eq(&self, other: &Obj) -> bool {
+        if self.is_same(other) {
+            return true;
+        }
+        match (&*self.val.borrow(), &*other.val.borrow()) {
+            (ObjVal::Symbol(x), ObjVal::Symbol(y)) => x == y,
+            (ObjVal::Bool(x), ObjVal::Bool(y)) => x == y,
+            (ObjVal::Char(x), ObjVal::Char(y)) => x == y,
+            (ObjVal::Int(x), ObjVal::Int(y)) => x == y,
+            (ObjVal::Nil, ObjVal::Nil) => true,
+            (ObjVal::Primitive(x), ObjVal::Primitive(y)) => x == y,
+            (ObjVal::Alias(x), ObjVal::Alias(y)) => x == y,
+            _ => false,
+        }
+    }
+
+    /// eqv?, eq? that also compares floats. floats are eqv when they
+    /// are the same bits, so 0.0 and -0.0 are not.
+    pub fn is_eqv(&self, other: &Obj) -> bool {
+        if self.is_eq(other) {
+            return true;
+        }
+        match (&*self.val.borrow(), &*other.val.borrow()) {
+            (ObjVal::Float(x), ObjVal::Float(y)) => x.to_bits() == y.to_bits(),
+            _ => false,
+        }
+    }
+
+    /// equal?, the same structure with eqv? leaves and strings of the
+    /// same characters. procedures are only equal to themselves.
+    /// pairs already being compared are assumed equal, so comparing
+    /// cyclic structures terminates.
+    pub fn is_equal(&self, other: &Obj) -> bool {
+        let mut comparing = HashSet::new();
+        let mut todo = vec![(self.clone(), other.clone())];
+        while let Some((x, y)) = todo.pop() {
+            if x.is_eqv(&y) {
+                continue;
+            }
+            let addrs = (&*x.val as *const _ as usize, &*y.val as *const _ as usize);
+            if !comparing.insert(addrs) {
+                continue;
+            }
+            match (&*x.val.borrow(), &*y.val.borrow()) {
+                (ObjVal::Cons(xa, xd), ObjVal::Cons(ya, yd)) => {
+                    todo.push((xd.clone(), yd.clone()));
+                    todo.push((xa.clone(), ya.clone()));
+                }
+                (ObjVal::Str(xs), ObjVal::Str(ys)) if xs == ys => (),
+                _ => return false,
+            }
         }
+        true
     }
 }
 
@@ -457,7 +905,7 @@ mod tests {
 
     fn get_parser(s: &str) -> Parser {
         let lexer = Lexer::new(s, "test.scm");
-        Parser::new(lexer)
+        Parser::new(lexer).unwrap()
     }
 
     fn get_obj(s: &str) -> Obj {
@@ -472,6 +920,77 @@ mod tests {
         assert!(objtree.is_definition());
     }
 
+    #[test]
+    fn to_symb_keeps_position() {
+        let objtree = get_obj("(define foo 42)");
+        let foo = objtree.cadr().unwrap();
+        assert_eq!(foo.to_symb().unwrap(), Symb::intern("foo"));
+        let loc = foo.loc.clone().unwrap();
+        assert_eq!(loc.filename(), "test.scm");
+        assert_eq!(loc.start, 8);
+    }
+
+    #[test]
+    fn equivalence() {
+        let xs = get_obj("(a (1 2.5) \"s\" #\\c)");
+        let ys = get_obj("(a (1 2.5) \"s\" #\\c)");
+        assert!(xs.is_eq(&xs));
+        assert!(!xs.is_eq(&ys));
+        assert!(!xs.is_eqv(&ys));
+        assert!(xs.is_equal(&ys));
+        // symbols, ints and chars are immediates.
+        assert!(xs.car().unwrap().is_eq(&ys.car().unwrap()));
+        assert!(xs.cadddr().unwrap().is_eq(&ys.cadddr().unwrap()));
+        let (x, y) = (Obj::new_int(1, None), Obj::new_int(1, None));
+        assert!(x.is_eq(&y));
+        let (x, y) = (Obj::new_float(2.5, None), Obj::new_float(2.5, None));
+        assert!(!x.is_eq(&y) && x.is_eqv(&y));
+        assert!(!Obj::new_float(0.0, None).is_eqv(&Obj::new_float(-0.0, None)));
+        assert!(!get_obj("(1 2)").is_equal(&get_obj("(1 2 3)")));
+        assert!(!get_obj("(1 . 2)").is_equal(&get_obj("(1 . 2.0)")));
+    }
+
+    #[test]
+    fn equal_on_cycles() {
+        // two rings, one of period 1 and one of period 2.
+        let ring = |n: usize| {
+            let cells: Vec<Obj> = (0..n)
+                .map(|_| Obj::cons(Obj::new_int(1, None), Obj::nil(None)))
+                .collect();
+            for i in 0..n {
+                let next = cells[(i + 1) % n].clone();
+                *cells[i].val.borrow_mut() = ObjVal::Cons(Obj::new_int(1, None), next);
+            }
+            cells[0].clone()
+        };
+        assert!(ring(1).is_equal(&ring(2)));
+        let other = Obj::cons(Obj::new_int(2, None), ring(1));
+        assert!(!other.is_equal(&ring(2)));
+    }
+
+    #[test]
+    fn describe_type() {
+        let xs = get_obj("(a 1 2.5 \"s\" #\\c (b . c) ())");
+        let types: Vec<String> = xs
+            .list_to_vec()
+            .unwrap()
+            .iter()
+            .map(|x| x.describe_type().to_owned())
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                "symbol",
+                "int",
+                "float",
+                "string",
+                "char",
+                "pair",
+                "empty list"
+            ]
+        );
+    }
+
     #[test]
     fn definition_variable() {
         let _ = (|| -> EvalResult<()> {
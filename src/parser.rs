@@ -1,40 +1,77 @@
+use crate::diagnostic::Diagnostic;
 use crate::lexer;
+use crate::lexer::LexError;
 use crate::types::*;
+use std::fmt;
 
-pub type ParserResult = Result<Ast, String>;
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub msg: String,
+    pub loc: Loc,
+    pub help: Option<String>,
+}
+
+impl ParseError {
+    fn new(msg: &str, loc: Loc) -> ParseError {
+        ParseError {
+            msg: msg.to_owned(),
+            loc,
+            help: None,
+        }
+    }
+
+    fn with_help(mut self, help: &str) -> ParseError {
+        self.help = Some(help.to_owned());
+        self
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(&self.msg).at(Some(self.loc.clone()));
+        match &self.help {
+            Some(help) => diagnostic.with_help(help),
+            None => diagnostic,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.loc, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub type ParserResult = Result<Ast, ParseError>;
 
 // ------------------------------------------------------------------
 pub struct Parser {
-    filename: String,
     toks: Vec<Token>,
     idx: usize,
-}
-
-fn err(msg: &str) -> ParserResult {
-    Err(msg.to_owned())
+    /// an empty loc just past the last byte of the input.
+    eof: Loc,
 }
 
 impl Parser {
-    pub fn new(lexer: lexer::Lexer) -> Parser {
+    /// lex the whole input up front, failing on the first lex error.
+    pub fn new(lexer: lexer::Lexer) -> Result<Parser, LexError> {
         let mut toks = vec![];
-        let filename = lexer.filename.clone();
+        let file = lexer.file.clone();
 
         for span in lexer {
-            if let Ok(token) = span {
-                if token.tok == Tok::Space {
-                    continue;
-                }
-                toks.push(token);
-            } else {
-                panic!("failed to lex a string");
+            let token = span?;
+            if token.is_atmosphere() {
+                continue;
             }
+            toks.push(token);
         }
 
-        Parser {
-            filename,
+        let len = file.source().text.len();
+        Ok(Parser {
             toks,
             idx: 0,
-        }
+            eof: Loc::new(file, len, len),
+        })
     }
 
     fn next_token(&mut self) -> Option<&Token> {
@@ -47,51 +84,140 @@ impl Parser {
         }
     }
 
+    fn peek_token(&self) -> Option<&Token> {
+        self.toks.get(self.idx)
+    }
+
+    // the loc of the token at cursor, or the end of input.
+    fn loc_at(&self, cursor: usize) -> Loc {
+        match self.toks.get(cursor) {
+            Some(token) => token.loc(),
+            None => self.eof.clone(),
+        }
+    }
+
+    // what was found at cursor, for error messages.
+    fn found_at(&self, cursor: usize) -> String {
+        match self.toks.get(cursor) {
+            Some(token) => format!("`{}`", token.text()),
+            None => "end of input".to_owned(),
+        }
+    }
+
     fn err(&mut self, cursor: usize, msg: &str) -> ParserResult {
         self.idx = cursor;
-        err(msg)
+        Err(ParseError::new(msg, self.loc_at(cursor)))
     }
 
-    fn err_plus(&mut self, cursor: usize, msg1: String, msg2: &str) -> ParserResult {
-        self.idx = cursor;
-        Err(msg1 + "\n " + &self.current_token_pos() + msg2)
+    // true at the end of input or at a token that closes or dots the
+    // enclosing list, where no datum can start.
+    fn at_datum_end(&self) -> bool {
+        match self.peek_token() {
+            Some(token) => token.is_rparen() || token.is_dot(),
+            None => true,
+        }
     }
 
-    fn current_token_pos(&self) -> String {
-        if self.toks.len() == 0 {
-            self.filename.clone() + ": end of file"
-        } else {
-            let pos = format!("@ char: {}\n", self.toks[0].start).to_owned();
-            self.filename.clone() + ": " + &pos
+    // #; comments out the datum that follows it, which may itself be
+    // preceded by more datum comments: #; #; a b skips both a and b.
+    fn skip_datum_comments(&mut self) -> Result<(), ParseError> {
+        while let Some(tok) = self.peek_token() {
+            if !tok.is_datum_comment() {
+                break;
+            }
+            let loc = tok.loc();
+            self.idx += 1;
+            if self.at_datum_end() {
+                return Err(ParseError::new("datum comment is missing its datum", loc));
+            }
+            self.expr()?;
         }
+        Ok(())
     }
 
     // RULES ------------------------------------------------------------------
+
+    /// every top level datum in the source, in order. unlike list,
+    /// this fails if anything is left over after the last datum.
+    pub fn program(&mut self) -> Result<Vec<Ast>, ParseError> {
+        let mut forms = vec![];
+        loop {
+            self.skip_datum_comments()?;
+            match self.peek_token() {
+                None => return Ok(forms),
+                Some(token) if token.is_rparen() => {
+                    let err = ParseError::new("unexpected `)`", token.loc());
+                    return Err(err.with_help("this `)` has no matching `(`"));
+                }
+                Some(_) => forms.push(self.expr()?),
+            }
+        }
+    }
+
     pub fn list(&mut self) -> ParserResult {
         let idx = self.idx;
 
         match (|| {
-            self.lparen()?;
-            let xs = self.exprs()?;
-            self.rparen()?;
-            Ok(xs)
-        })() as ParserResult
+            self.skip_datum_comments()?;
+            let open = self.lparen()?.loc();
+            let mut xs = self.exprs()?;
+            let dotted = self.dotted_tail(&mut xs)?;
+            self.skip_datum_comments()?;
+
+            let close = match self.peek_token() {
+                Some(token) if token.is_rparen() => self.rparen()?.loc(),
+                Some(_) => {
+                    let found = self.found_at(self.idx);
+                    let err = if dotted {
+                        format!(
+                            "expected `)` after the tail of a dotted list, found {}",
+                            found
+                        )
+                    } else {
+                        format!("expected `)`, found {}", found)
+                    };
+                    return Err(ParseError::new(&err, self.loc_at(self.idx)));
+                }
+                None => {
+                    let err = ParseError::new("unclosed list", open.unwrap_or(self.eof.clone()));
+                    return Err(err.with_help("add a `)` to close it"));
+                }
+            };
+            if let (Some(open), Some(close)) = (open, close) {
+                xs.set_loc(open.to(&close));
+            }
+            Ok((xs, dotted))
+        })() as Result<(Ast, bool), ParseError>
         {
-            Ok(mut xs) => {
+            Ok((mut xs, dotted)) => {
                 // xs is has rule type Exprs, which is zero-or-more
                 // expressions, but this is a List production, but
                 // that that makes the AST more cumbersome, so flatten
                 // it.
-                xs.replace_rule(Rule::List);
+                xs.replace_rule(if dotted { Rule::DottedList } else { Rule::List });
                 return Ok(xs);
             }
-            Err(msg) => self.err_plus(idx, msg, "list fails"),
+            Err(err) => {
+                self.idx = idx;
+                Err(err)
+            }
         }
     }
 
     pub fn expr(&mut self) -> ParserResult {
         let idx = self.idx;
 
+        if let Err(err) = self.skip_datum_comments() {
+            self.idx = idx;
+            return Err(err);
+        }
+
+        match self.peek_token() {
+            Some(token) if token.is_lparen() => return self.list(),
+            Some(token) if token.is_abbreviation() => return self.abbreviation(),
+            _ => {}
+        }
+
         // this is boiler plate.
         if let Ok(n) = self.float() {
             return Ok(n);
@@ -102,32 +228,101 @@ impl Parser {
         if let Ok(n) = self.symbol() {
             return Ok(n);
         }
+        if let Ok(n) = self.string() {
+            return Ok(n);
+        }
+        if let Ok(n) = self.character() {
+            return Ok(n);
+        }
+
+        let msg = format!("expected a datum, found {}", self.found_at(self.idx));
+        let cursor = self.idx;
+        self.err(cursor, &msg)
+    }
 
-        let result = self.list();
-        // TODO perhaps use a vector of error strings to trace the
-        // errors instead of using string append.
-        match result {
-            Ok(n) => Ok(n),
-            Err(msg) => self.err_plus(
-                idx,
-                msg + &self.current_token_pos(),
-                "expr fails to parse expr",
-            ),
+    // the optional `. datum` that ends an improper list. the tail is
+    // pushed onto the nodes of xs, returns whether there was one.
+    fn dotted_tail(&mut self, xs: &mut Ast) -> Result<bool, ParseError> {
+        let idx = self.idx;
+        let dot = match self.next_token() {
+            Some(tok) if tok.is_dot() => tok.loc(),
+            _ => {
+                self.idx = idx;
+                return Ok(false);
+            }
+        };
+        match xs {
+            Ast::Node { nodes, .. } if !nodes.is_empty() => {
+                self.skip_datum_comments()?;
+                if self.at_datum_end() {
+                    return Err(ParseError::new("expected a datum after `.`", dot));
+                }
+                let tail = self.expr()?;
+                nodes.push(tail);
+                Ok(true)
+            }
+            _ => {
+                self.idx = idx;
+                Err(ParseError::new(
+                    "a dot must follow at least one datum in a list",
+                    dot,
+                ))
+            }
         }
     }
 
-    // this can't fail.
+    // zero or more expressions, up to the end of input or a token
+    // that can't start one. a datum that starts but fails to parse
+    // is an error.
     fn exprs(&mut self) -> ParserResult {
         //println!("exprs");
         let mut nodes = vec![];
         loop {
-            let idx = self.idx;
-            if let Ok(n) = self.expr() {
-                nodes.push(n);
-            } else {
-                self.idx = idx;
+            self.skip_datum_comments()?;
+            if self.at_datum_end() {
                 return Ok(Ast::node(Rule::Exprs, nodes));
             }
+            nodes.push(self.expr()?);
+        }
+    }
+
+    // 'x `x ,x and ,@x read as (quote x), (quasiquote x), (unquote
+    // x) and (unquote-splicing x).
+    fn abbreviation(&mut self) -> ParserResult {
+        let idx = self.idx;
+        let token = match self.next_token() {
+            Some(token) if token.is_abbreviation() => token.clone(),
+            _ => return self.err(idx, "expected a quote abbreviation"),
+        };
+        let name = token.tok.abbreviation().unwrap();
+
+        if let Err(err) = self.skip_datum_comments() {
+            self.idx = idx;
+            return Err(err);
+        }
+        if self.at_datum_end() {
+            let msg = format!("`{}` is missing its datum", token.text());
+            return self.err(idx, &msg);
+        }
+        match self.expr() {
+            Ok(datum) => {
+                let symb = Symb::intern(name);
+                let head = Token::new(
+                    Tok::Symbol(symb),
+                    token.file.clone(),
+                    token.start,
+                    token.end,
+                );
+                let loc = match datum.loc() {
+                    Some(end) => token.loc().to(&end),
+                    None => token.loc(),
+                };
+                Ok(Ast::node_at(Rule::List, vec![Ast::leaf(head), datum], loc))
+            }
+            Err(err) => {
+                self.idx = idx;
+                Err(err)
+            }
         }
     }
 
@@ -139,25 +334,28 @@ impl Parser {
                 if tok.is_lparen() {
                     Ok(Ast::Leaf(tok.clone()))
                 } else {
-                    self.err(idx, "lparen got wrong token")
+                    let msg = format!("expected `(`, found {}", self.found_at(idx));
+                    self.err(idx, &msg)
                 }
             }
-            None => self.err(idx, "done"),
+            None => self.err(idx, "expected `(`, found end of input"),
         }
     }
 
     fn rparen(&mut self) -> ParserResult {
         //println!("rparen");
         let idx = self.idx;
+        self.skip_datum_comments()?;
         match self.next_token() {
             Some(token) => {
                 if token.is_rparen() {
                     Ok(Ast::Leaf(token.clone()))
                 } else {
-                    self.err(idx, "rparen got wrong token")
+                    let msg = format!("expected `)`, found {}", self.found_at(idx));
+                    self.err(idx, &msg)
                 }
             }
-            None => self.err(idx, "done"),
+            None => self.err(idx, "expected `)`, found end of input"),
         }
     }
 
@@ -168,10 +366,10 @@ impl Parser {
             if token.is_int() {
                 Ok(Ast::Leaf(token.clone()))
             } else {
-                self.err(idx, "todo int err msg")
+                self.err(idx, "expected an integer")
             }
         } else {
-            self.err(idx, "todo int err msg 2")
+            self.err(idx, "expected an integer, found end of input")
         }
     }
 
@@ -182,10 +380,36 @@ impl Parser {
             if token.is_float() {
                 Ok(Ast::Leaf(token.clone()))
             } else {
-                self.err(idx, "todo float err msg")
+                self.err(idx, "expected a float")
+            }
+        } else {
+            self.err(idx, "expected a float, found end of input")
+        }
+    }
+
+    fn string(&mut self) -> ParserResult {
+        let idx = self.idx;
+        if let Some(token) = self.next_token() {
+            if token.is_str() {
+                Ok(Ast::Leaf(token.clone()))
+            } else {
+                self.err(idx, "expected a string")
+            }
+        } else {
+            self.err(idx, "expected a string, found end of input")
+        }
+    }
+
+    fn character(&mut self) -> ParserResult {
+        let idx = self.idx;
+        if let Some(token) = self.next_token() {
+            if token.is_char() {
+                Ok(Ast::Leaf(token.clone()))
+            } else {
+                self.err(idx, "expected a character")
             }
         } else {
-            self.err(idx, "todo float err msg 2")
+            self.err(idx, "expected a character, found end of input")
         }
     }
 
@@ -196,10 +420,10 @@ impl Parser {
             if token.is_symbol() {
                 Ok(Ast::Leaf(token.clone()))
             } else {
-                self.err(idx, "todo symbol err msg")
+                self.err(idx, "expected a symbol")
             }
         } else {
-            self.err(idx, "todo symbol err msg 2")
+            self.err(idx, "expected a symbol, found end of input")
         }
     }
 }
@@ -217,7 +441,7 @@ mod tests {
         let mut toks = vec![];
         for span in lexer {
             if let Ok(token) = span {
-                if token.tok == Tok::Space {
+                if token.is_atmosphere() {
                     continue;
                 }
                 println!("token: {:?}", token);
@@ -233,7 +457,7 @@ mod tests {
     fn get_parser(s: &str) -> Parser {
         let lexer = Lexer::new(s, "test.scm");
         println!("lexer: {:?}", get_tokens(s));
-        Parser::new(lexer)
+        Parser::new(lexer).unwrap()
     }
 
     #[test]
@@ -242,7 +466,7 @@ mod tests {
         let results = parser.list();
 
         match results {
-            Err(msg) => panic!(msg),
+            Err(msg) => panic!("{}", msg),
             Ok(xs) => {
                 let obj = xs.to_obj();
                 assert!(obj.is_list());
@@ -273,7 +497,7 @@ mod tests {
 
         match results {
             Ok(node) => match &node {
-                Ast::Node { rule, nodes: _ } => {
+                Ast::Node { rule, .. } => {
                     println!("{:?}", node);
                     node.pretty();
                     assert_eq!(rule, &Rule::List);
@@ -281,7 +505,7 @@ mod tests {
                 _ => panic!("This should not be a leaf!"),
             },
             Err(msg) => {
-                panic!(msg);
+                panic!("{}", msg);
             }
         }
     }
@@ -293,13 +517,13 @@ mod tests {
 
         match results {
             Ok(xs) => match xs {
-                Ast::Node { rule: _, nodes } => {
+                Ast::Node { nodes, .. } => {
                     assert_eq!(nodes.len(), 4);
                 }
                 _ => panic!("This should not be a leaf!"),
             },
             Err(msg) => {
-                panic!(msg);
+                panic!("{}", msg);
             }
         }
     }
@@ -308,7 +532,7 @@ mod tests {
     fn parse_list_many() {
         let mut parser = get_parser("(1 2 3 4)");
         if let Err(msg) = parser.list() {
-            panic!(msg);
+            panic!("{}", msg);
         }
     }
 
@@ -316,7 +540,7 @@ mod tests {
     fn parse_list1() {
         let mut parser = get_parser("( 1 )");
         if let Err(msg) = parser.list() {
-            panic!(msg);
+            panic!("{}", msg);
         }
     }
 
@@ -324,7 +548,7 @@ mod tests {
     fn parse_expr_int() {
         let mut parser = get_parser("2");
         if let Err(msg) = parser.expr() {
-            panic!(msg);
+            panic!("{}", msg);
         }
     }
 
@@ -332,7 +556,7 @@ mod tests {
     fn parse_expr_symbol() {
         let mut parser = get_parser("asdf");
         if let Err(msg) = parser.expr() {
-            panic!(msg);
+            panic!("{}", msg);
         }
     }
 
@@ -340,7 +564,7 @@ mod tests {
     fn parse_unicode_list() {
         let mut parser = get_parser("( ε )");
         if let Err(msg) = parser.list() {
-            panic!(msg);
+            panic!("{}", msg);
         }
     }
 
@@ -348,7 +572,7 @@ mod tests {
     fn parse_unicode_symbol1() {
         let mut parser = get_parser("asdfε");
         if let Err(msg) = parser.expr() {
-            panic!(msg);
+            panic!("{}", msg);
         }
     }
 
@@ -356,7 +580,7 @@ mod tests {
     fn parse_unicode_symbol2() {
         let mut parser = get_parser("εasdf");
         if let Err(msg) = parser.expr() {
-            panic!(msg);
+            panic!("{}", msg);
         }
     }
 
@@ -364,10 +588,199 @@ mod tests {
     fn parse_unicode_symbol() {
         let mut parser = get_parser("ε");
         if let Err(msg) = parser.expr() {
-            panic!(msg);
+            panic!("{}", msg);
+        }
+    }
+
+    #[test]
+    fn parse_string_list() {
+        let mut parser = get_parser(r#"(string-append "a" "b")"#);
+        match parser.list() {
+            Ok(node) => assert!(node.to_obj().cadr().unwrap().is_str()),
+            Err(msg) => panic!("{}", msg),
         }
     }
 
+    #[test]
+    fn parse_comments() {
+        let prog = "; leading comment\n(1 #| two |# 3 ; four\n 5)";
+        let mut parser = get_parser(prog);
+        let obj = parser.list().unwrap().to_obj();
+        assert_eq!(obj.list_length().unwrap(), 3);
+    }
+
+    #[test]
+    fn parse_datum_comments() {
+        let mut parser = get_parser("(1 #;(2 (3)) 4 #; 5)");
+        let obj = parser.list().unwrap().to_obj();
+        assert_eq!(obj.list_length().unwrap(), 2);
+        assert_eq!(obj.cadr().unwrap(), Obj::new_int(4, None));
+
+        let mut parser = get_parser("(#; #; 1 2 3)");
+        let obj = parser.list().unwrap().to_obj();
+        assert_eq!(obj, Obj::list_from_vec(vec![Obj::new_int(3, None)], None));
+
+        let mut parser = get_parser("#;(skipped) (kept)");
+        let obj = parser.list().unwrap().to_obj();
+        assert!(obj.car().unwrap().string_matches("kept"));
+    }
+
+    #[test]
+    fn parse_datum_comment_without_datum() {
+        let mut parser = get_parser("(1 #;)");
+        assert!(parser.list().is_err());
+    }
+
+    #[test]
+    fn parse_quote_abbreviations() {
+        let mut parser = get_parser("('a `(b ,c ,@d))");
+        let obj = parser.list().unwrap().to_obj();
+        let quoted = obj.car().unwrap();
+        assert!(quoted.is_tagged_list(Symb::QUOTE));
+        assert!(quoted.cadr().unwrap().string_matches("a"));
+
+        let quasi = obj.cadr().unwrap();
+        assert!(quasi.is_tagged_list(Symb::QUASIQUOTE));
+        let template = quasi.cadr().unwrap();
+        assert!(template.cadr().unwrap().is_tagged_list(Symb::UNQUOTE));
+        assert!(template
+            .caddr()
+            .unwrap()
+            .is_tagged_list(Symb::UNQUOTE_SPLICING));
+    }
+
+    #[test]
+    fn parse_quote_without_datum() {
+        let mut parser = get_parser("(a ')");
+        assert!(parser.list().is_err());
+    }
+
+    #[test]
+    fn parse_dotted_pair() {
+        let mut parser = get_parser("(a . b)");
+        let ast = parser.list().unwrap();
+        if let Ast::Node { rule, .. } = &ast {
+            assert_eq!(rule, &Rule::DottedList);
+        }
+        let obj = ast.to_obj();
+        assert!(!obj.is_list());
+        assert!(obj.car().unwrap().string_matches("a"));
+        assert!(obj.cdr().unwrap().string_matches("b"));
+    }
+
+    #[test]
+    fn parse_improper_list() {
+        let mut parser = get_parser("(1 2 . (3 . 4))");
+        let obj = parser.list().unwrap().to_obj();
+        assert_eq!(format!("{:?}", obj), "(1 . (2 . (3 . 4)))");
+
+        // a proper tail gives back a proper list.
+        let mut parser = get_parser("(1 . (2))");
+        assert!(parser.list().unwrap().to_obj().is_list());
+    }
+
+    #[test]
+    fn parse_bad_dotted_lists() {
+        assert!(get_parser("(. a)").list().is_err());
+        assert!(get_parser("(a .)").list().is_err());
+        assert!(get_parser("(a . b c)").list().is_err());
+    }
+
+    #[test]
+    fn parse_program() {
+        let mut parser = get_parser("(define x 1) x 2.0 \"s\" 'q #;(skipped) (list x)");
+        let forms = parser.program().unwrap();
+        assert_eq!(forms.len(), 6);
+        assert!(forms[1].is_symbol());
+        assert!(forms[5].to_obj().is_tagged_list(Symb::intern("list")));
+    }
+
+    #[test]
+    fn parse_program_empty() {
+        let mut parser = get_parser("  ; nothing here\n #| or here |#");
+        assert_eq!(parser.program().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn parse_program_leftovers_fail() {
+        assert!(get_parser("(a) b)").program().is_err());
+        assert!(get_parser("(a) (b").program().is_err());
+    }
+
+    #[test]
+    fn parse_lex_errors() {
+        let lex_err = |s: &str| Parser::new(Lexer::new(s, "test.scm")).err().unwrap();
+        assert_eq!(
+            lex_err("(a [b])"),
+            LexError::UnexpectedChar {
+                c: '[',
+                start: 3,
+                end: 4
+            }
+        );
+        assert_eq!(
+            lex_err("(a \"bc"),
+            LexError::Unterminated {
+                what: "string",
+                start: 3,
+                end: 6
+            }
+        );
+        assert_eq!(lex_err("(1 12x3 4)").span(), (3, 7));
+    }
+
+    #[test]
+    fn parse_locations() {
+        let mut parser = get_parser("(define x\n  (f 'y \"s\"))");
+        let obj = parser.list().unwrap().to_obj();
+        let loc = obj.loc.clone().unwrap();
+        assert_eq!((loc.start, loc.end), (0, 23));
+        assert_eq!(loc.filename(), "test.scm");
+
+        let call = obj.caddr().unwrap();
+        assert_eq!(call.loc.clone().unwrap().line_col(), (2, 3));
+        let quoted = call.cadr().unwrap();
+        assert_eq!(quoted.loc.clone().unwrap().line_col(), (2, 6));
+        let string = call.caddr().unwrap();
+        assert_eq!(format!("{}", string.loc.clone().unwrap()), "test.scm:2:9");
+    }
+
+    fn parse_error(s: &str) -> ParseError {
+        get_parser(s).program().err().unwrap()
+    }
+
+    #[test]
+    fn parse_error_locations() {
+        let err = parse_error("(a)\n(b (c d)");
+        assert_eq!(err.msg, "unclosed list");
+        assert_eq!(err.loc.line_col(), (2, 1));
+        assert!(err.help.is_some());
+
+        let err = parse_error("(a b))");
+        assert_eq!(err.msg, "unexpected `)`");
+        assert_eq!((err.loc.start, err.loc.end), (5, 6));
+
+        let err = parse_error("(a . b c)");
+        assert_eq!(
+            err.msg,
+            "expected `)` after the tail of a dotted list, found `c`"
+        );
+        assert_eq!(err.loc.start, 7);
+
+        let err = parse_error("(a ')");
+        assert_eq!(err.msg, "`'` is missing its datum");
+        assert_eq!(err.loc.start, 3);
+    }
+
+    #[test]
+    fn parse_error_diagnostic() {
+        let rendered = parse_error("(define x\n  (f 1)").to_diagnostic().render();
+        assert_eq!(
+            rendered,
+            "error: unclosed list\n --> test.scm:1:1\n  |\n1 | (define x\n  | ^\n  = help: add a `)` to close it\n"
+        );
+    }
+
     #[test]
     fn parse_lparen() {
         let mut parser = get_parser("(");
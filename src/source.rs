@@ -0,0 +1,124 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+// Source files. The program text handed to the lexer is kept in a
+// SourceFile, and the tokens and locations made from it share it
+// through a FileId, so a Loc stays small and cheap to clone while
+// still being able to produce file:line:col and the text of the line
+// for error messages. The text is freed with the last FileId of it.
+
+/// a handle on a source text. handles are equal when they are of the
+/// same text, the same name may be used for more than one.
+#[derive(Clone)]
+pub struct FileId(Arc<SourceFile>);
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    /// byte offset of the first character of every line.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: &str, text: &str) -> SourceFile {
+        let mut line_starts = vec![0];
+        for (i, c) in text.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        SourceFile {
+            name: name.to_owned(),
+            text: text.to_owned(),
+            line_starts,
+        }
+    }
+
+    /// 1-based line and column of a byte offset, columns count
+    /// characters rather than bytes.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.line_starts[line];
+        let col = self.text[start..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    /// the text of a 1-based line, without its line ending.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = match self.line_starts.get(line) {
+            Some(next) => *next,
+            None => self.text.len(),
+        };
+        Some(self.text[start..end].trim_end_matches(['\n', '\r']))
+    }
+
+    /// byte offset where a 1-based line starts.
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.line_starts.get(line.checked_sub(1)?).cloned()
+    }
+}
+
+impl FileId {
+    /// a program text under a file name.
+    pub fn new(name: &str, text: &str) -> FileId {
+        FileId(Arc::new(SourceFile::new(name, text)))
+    }
+
+    pub fn source(&self) -> &SourceFile {
+        &self.0
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+}
+
+impl PartialEq for FileId {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for FileId {}
+
+impl Hash for FileId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state)
+    }
+}
+
+impl fmt::Debug for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FileId({})", self.0.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_line_col() {
+        let file = FileId::new("test.scm", "(a\n  bc\n\nλd)");
+        let source = file.source();
+        assert_eq!(source.line_col(0), (1, 1));
+        assert_eq!(source.line_col(1), (1, 2));
+        assert_eq!(source.line_col(5), (2, 3));
+        assert_eq!(source.line_col(8), (3, 1));
+        // λ is two bytes but one column.
+        assert_eq!(source.line_col(11), (4, 2));
+        assert_eq!(source.line(2), Some("  bc"));
+        assert_eq!(source.line(3), Some(""));
+        assert_eq!(source.line(5), None);
+        assert_eq!(file.name(), "test.scm");
+        assert_ne!(file, FileId::new("test.scm", "(a\n  bc\n\nλd)"));
+        assert_eq!(file, file.clone());
+    }
+}
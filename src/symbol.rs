@@ -0,0 +1,211 @@
+use crate::types::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+// The symbol table. Every symbol name is interned here once and a
+// Symb is just its index, so comparing and hashing symbols is
+// comparing and hashing integers. Names are never freed, they are
+// leaked to get &'static str out of the table.
+//
+// A fresh symbol, a renamed identifier of a macro expansion or a
+// gensym, shares the name of the symbol it was made from and is told
+// apart by a mark from a counter, so making one doesn't grow the
+// table.
+//
+// The special forms the evaluator dispatches on and the symbols it
+// returns are interned first, in the order of WELL_KNOWN, so they are
+// constants.
+
+const WELL_KNOWN: [&str; 31] = [
+    "quote",
+    "quasiquote",
+    "unquote",
+    "unquote-splicing",
+    "set!",
+    "define",
+    "if",
+    "lambda",
+    "begin",
+    "ok",
+    "else",
+    "case-lambda",
+    "let",
+    "let*",
+    "letrec",
+    "letrec*",
+    "cond",
+    "case",
+    "when",
+    "unless",
+    "and",
+    "or",
+    "=>",
+    "do",
+    "define-syntax",
+    "let-syntax",
+    "letrec-syntax",
+    "syntax-rules",
+    "...",
+    "_",
+    "define-macro",
+];
+
+impl Symb {
+    const fn interned(id: u32) -> Symb {
+        Symb { id, mark: 0 }
+    }
+
+    pub const QUOTE: Symb = Symb::interned(0);
+    pub const QUASIQUOTE: Symb = Symb::interned(1);
+    pub const UNQUOTE: Symb = Symb::interned(2);
+    pub const UNQUOTE_SPLICING: Symb = Symb::interned(3);
+    pub const SET: Symb = Symb::interned(4);
+    pub const DEFINE: Symb = Symb::interned(5);
+    pub const IF: Symb = Symb::interned(6);
+    pub const LAMBDA: Symb = Symb::interned(7);
+    pub const BEGIN: Symb = Symb::interned(8);
+    pub const OK: Symb = Symb::interned(9);
+    pub const ELSE: Symb = Symb::interned(10);
+    pub const CASE_LAMBDA: Symb = Symb::interned(11);
+    pub const LET: Symb = Symb::interned(12);
+    pub const LET_STAR: Symb = Symb::interned(13);
+    pub const LETREC: Symb = Symb::interned(14);
+    pub const LETREC_STAR: Symb = Symb::interned(15);
+    pub const COND: Symb = Symb::interned(16);
+    pub const CASE: Symb = Symb::interned(17);
+    pub const WHEN: Symb = Symb::interned(18);
+    pub const UNLESS: Symb = Symb::interned(19);
+    pub const AND: Symb = Symb::interned(20);
+    pub const OR: Symb = Symb::interned(21);
+    pub const ARROW: Symb = Symb::interned(22);
+    pub const DO: Symb = Symb::interned(23);
+    pub const DEFINE_SYNTAX: Symb = Symb::interned(24);
+    pub const LET_SYNTAX: Symb = Symb::interned(25);
+    pub const LETREC_SYNTAX: Symb = Symb::interned(26);
+    pub const SYNTAX_RULES: Symb = Symb::interned(27);
+    pub const ELLIPSIS: Symb = Symb::interned(28);
+    pub const UNDERSCORE: Symb = Symb::interned(29);
+    pub const DEFINE_MACRO: Symb = Symb::interned(30);
+}
+
+struct SymbolTable {
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, Symb>,
+}
+
+impl SymbolTable {
+    fn new() -> SymbolTable {
+        let mut table = SymbolTable {
+            names: vec![],
+            ids: HashMap::new(),
+        };
+        for name in WELL_KNOWN.iter() {
+            table.intern(name);
+        }
+        table
+    }
+
+    fn intern(&mut self, name: &str) -> Symb {
+        if let Some(symb) = self.ids.get(name) {
+            return *symb;
+        }
+        let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        let symb = Symb::interned(self.names.len() as u32);
+        self.names.push(name);
+        self.ids.insert(name, symb);
+        symb
+    }
+}
+
+static SYMBOLS: OnceLock<Mutex<SymbolTable>> = OnceLock::new();
+
+// the last mark given to a fresh symbol. it is 64 bits so it never
+// wraps around to a mark that is still in use, or to 0.
+static MARKS: AtomicU64 = AtomicU64::new(0);
+
+fn symbols() -> &'static Mutex<SymbolTable> {
+    SYMBOLS.get_or_init(|| Mutex::new(SymbolTable::new()))
+}
+
+impl Symb {
+    /// the symbol for a name, the same name always gives the same symbol.
+    pub fn intern(name: &str) -> Symb {
+        symbols().lock().unwrap().intern(name)
+    }
+
+    /// a new symbol with the name of base that is different from every
+    /// other symbol. it takes no room in the symbol table.
+    pub fn fresh(base: Symb) -> Symb {
+        let mark = MARKS.fetch_add(1, Ordering::Relaxed) + 1;
+        Symb { id: base.id, mark }
+    }
+
+    /// a fresh symbol named prefix, for gensym. it prints with its
+    /// mark so gensyms are told apart when they are written out.
+    pub fn gensym(prefix: &str) -> Symb {
+        Symb::fresh(Symb::intern(prefix))
+    }
+
+    /// one of the symbols interned first, the special forms and the
+    /// keywords they use. they are never looked up as macros.
+    pub fn is_well_known(&self) -> bool {
+        self.mark == 0 && (self.id as usize) < WELL_KNOWN.len()
+    }
+
+    pub fn name(&self) -> &'static str {
+        symbols().lock().unwrap().names[self.id as usize]
+    }
+}
+
+// fresh symbols are written with their mark after a dot, g.17, so
+// that g1 and a gensym made from g don't look alike.
+impl fmt::Display for Symb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        if self.mark != 0 {
+            write!(f, ".{}", self.mark)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Symb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Symb({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_names() {
+        let a = Symb::intern("a-symbol");
+        assert_eq!(a, Symb::intern("a-symbol"));
+        assert_ne!(a, Symb::intern("another-symbol"));
+        assert_eq!(a.name(), "a-symbol");
+        assert_eq!(Symb::intern("lambda"), Symb::LAMBDA);
+        for (i, name) in WELL_KNOWN.iter().enumerate() {
+            assert_eq!(Symb::interned(i as u32).name(), *name);
+        }
+        assert_eq!(Symb::UNQUOTE_SPLICING.name(), "unquote-splicing");
+        assert_eq!(Symb::ELSE.name(), "else");
+        let fresh = Symb::fresh(a);
+        assert_ne!(fresh, a);
+        assert_ne!(fresh, Symb::fresh(a));
+        assert_eq!(fresh.name(), "a-symbol");
+        assert!(!Symb::fresh(Symb::LAMBDA).is_well_known());
+        // it shares the table slot of its base.
+        assert_eq!(fresh.id, a.id);
+        assert_eq!(Symb::intern("a-symbol"), a);
+        let g = Symb::gensym("g");
+        assert_ne!(Symb::intern(&g.to_string()), g);
+        assert_ne!(Symb::gensym("g").to_string(), g.to_string());
+        assert_eq!(g.to_string(), format!("g.{}", g.mark));
+        assert_eq!(format!("{:?}", g), format!("Symb(g.{})", g.mark));
+        assert!(Symb::DEFINE_MACRO.is_well_known() && !g.is_well_known());
+    }
+}
@@ -0,0 +1,524 @@
+use crate::eval;
+use crate::types::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// syntax-rules macros. a use of a macro is matched against the
+// patterns of its rules in order and the template of the first rule
+// that matches is filled in with what the pattern variables matched.
+//
+// the expansion is hygienic by renaming: every identifier the template
+// puts into the expansion becomes an Alias of it, a fresh symbol that
+// remembers the environment the macro was defined in. a binding the
+// expansion makes is of the fresh symbol, so it can't capture the
+// user's variables, and an alias nothing in the expansion bound is
+// looked up where the macro was defined, so the user's bindings can't
+// capture the macro's free identifiers. keywords are recognized
+// through any number of aliases, see Obj::is_symb.
+//
+// define-macro and er-macro-transformer macros are procedures from
+// the form of the use to its expansion. define-macro is unhygienic,
+// the expansion means what it says where the macro is used. an
+// explicit-renaming transformer makes its own aliases with rename.
+
+/// (syntax-rules (⟨literal⟩ …) (⟨pattern⟩ ⟨template⟩) …), or with a
+/// custom ellipsis (syntax-rules ⟨ellipsis⟩ (⟨literal⟩ …) …), as a
+/// macro defined in env.
+pub fn syntax_rules(spec: &Obj, env: &Env) -> EvalResult<Macro> {
+    let mut rest = spec.cdr()?;
+    let mut ellipsis = Symb::ELLIPSIS;
+    if rest.car()?.is_symbol() {
+        ellipsis = rest.car()?.root_symb().unwrap();
+        rest = rest.cdr()?;
+    }
+    let mut literals = vec![];
+    for literal in rest.car()?.list_to_vec()? {
+        match literal.root_symb() {
+            Some(symb) => literals.push(symb),
+            None => return Err(LazarusError::wrong_type("identifier", &literal)),
+        }
+    }
+    let mut rules = vec![];
+    for rule in rest.cdr()?.list_to_vec()? {
+        rules.push(SyntaxRule {
+            pattern: rule.car()?,
+            template: rule.cadr()?,
+        });
+    }
+    Ok(Macro {
+        name: None,
+        transformer: Transformer::SyntaxRules(SyntaxRules {
+            ellipsis,
+            literals,
+            rules,
+        }),
+        env: env.clone(),
+    })
+}
+
+/// the expansion of form, a use of the macro in env.
+pub fn expand(mac: &Macro, form: &Obj, env: &Env) -> EvalResult<Obj> {
+    match &mac.transformer {
+        Transformer::SyntaxRules(rules) => {
+            let matcher = Matcher {
+                rules,
+                env: &mac.env,
+                use_env: env,
+            };
+            for rule in rules.rules.iter() {
+                let mut bindings = HashMap::new();
+                // the keyword in the pattern is ignored.
+                if matcher.match_pattern(&rule.pattern.cdr()?, &form.cdr()?, &mut bindings)? {
+                    let mut expansion = Expansion {
+                        rules,
+                        bindings,
+                        renames: HashMap::new(),
+                        env: &mac.env,
+                        loc: form.loc.clone(),
+                    };
+                    return expansion.instantiate(&rule.template, false);
+                }
+            }
+            let name = mac.name.map_or("macro", |name| name.name());
+            let msg = format!("no syntax-rules pattern of {} matches", name);
+            Err(LazarusError::eval(&msg).with_obj(form))
+        }
+        Transformer::Procedure {
+            procedure,
+            operands,
+        } => {
+            let arguments = if *operands {
+                form.cdr()?
+            } else {
+                Obj::list_from_vec(vec![form.clone()], form.loc.clone())
+            };
+            eval::apply(procedure.clone(), arguments)
+        }
+        Transformer::ExplicitRenaming(procedure) => {
+            RENAMERS.with(|renamers| {
+                renamers.borrow_mut().push(Renamer {
+                    env: mac.env.clone(),
+                    renames: HashMap::new(),
+                    loc: form.loc.clone(),
+                })
+            });
+            let arguments = vec![
+                form.clone(),
+                Obj::new_primitive("rename", rename, Arity::exactly(1)),
+                Obj::new_primitive("compare", compare, Arity::exactly(2)),
+            ];
+            let expansion = eval::apply(procedure.clone(), Obj::list_from_vec(arguments, None));
+            RENAMERS.with(|renamers| renamers.borrow_mut().pop());
+            expansion
+        }
+    }
+}
+
+/// (er-macro-transformer procedure), a macro whose expansion is
+/// (procedure form rename compare). the identifiers rename makes mean
+/// what they mean where procedure was made.
+pub fn er_macro_transformer(xs: Obj) -> EvalResult<Obj> {
+    let procedure = xs.car()?;
+    let mac = Macro {
+        name: None,
+        transformer: Transformer::ExplicitRenaming(procedure.clone()),
+        env: procedure.environment()?,
+    };
+    Ok(Obj::new(ObjVal::Macro(mac), procedure.loc.clone()))
+}
+
+// the expansions of explicit-renaming macros running right now, the
+// innermost last. rename and compare are plain primitives, so rename
+// finds the environment and the aliases of its expansion here.
+struct Renamer {
+    env: Env,
+    renames: HashMap<Symb, Obj>,
+    loc: Option<Loc>,
+}
+
+thread_local! {
+    static RENAMERS: RefCell<Vec<Renamer>> = const { RefCell::new(Vec::new()) };
+}
+
+// (rename identifier), the same alias for the same identifier during
+// one expansion.
+fn rename(xs: Obj) -> EvalResult<Obj> {
+    let id = xs.car()?;
+    let symb = id.to_symb()?;
+    RENAMERS.with(|renamers| match renamers.borrow_mut().last_mut() {
+        Some(renamer) => {
+            let (env, loc) = (&renamer.env, &renamer.loc);
+            Ok(renamer
+                .renames
+                .entry(symb)
+                .or_insert_with(|| new_alias(&id, env, loc.clone()))
+                .clone())
+        }
+        None => Err(LazarusError::eval(
+            "rename called outside of a macro expansion",
+        )),
+    })
+}
+
+// (compare a b), whether two identifiers are the same one once
+// renaming is undone.
+fn compare(xs: Obj) -> EvalResult<Obj> {
+    let (a, b) = (xs.car()?, xs.cadr()?);
+    let same = match (a.root_symb(), b.root_symb()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.is_eq(&b),
+    };
+    Ok(Obj::new_bool(same, None))
+}
+
+// an alias of id that means what it means in env.
+fn new_alias(id: &Obj, env: &Env, loc: Option<Loc>) -> Obj {
+    let alias = Alias {
+        symb: Symb::fresh(id.root_symb().unwrap()),
+        base: id.clone(),
+        env: env.clone(),
+    };
+    Obj::new(ObjVal::Alias(alias), loc)
+}
+
+// what a pattern variable matched, a pattern variable followed by n
+// ellipses matches a sequence nested n deep.
+#[derive(Clone)]
+enum Binding {
+    One(Obj),
+    Many(Vec<Binding>),
+}
+
+// the elements of a possibly improper list and what ends it, nil for
+// a proper list.
+fn split_list(xs: &Obj) -> EvalResult<(Vec<Obj>, Obj)> {
+    let mut items = vec![];
+    let mut xs = xs.clone();
+    while xs.is_cons() {
+        items.push(xs.car()?);
+        xs = xs.cdr()?;
+    }
+    Ok((items, xs))
+}
+
+impl SyntaxRules {
+    fn is_ellipsis(&self, x: &Obj) -> bool {
+        x.root_symb() == Some(self.ellipsis)
+    }
+
+    fn is_literal(&self, x: &Obj) -> bool {
+        match x.root_symb() {
+            Some(symb) => self.literals.contains(&symb),
+            None => false,
+        }
+    }
+
+    // the variables a pattern binds.
+    fn pattern_vars(&self, pattern: &Obj) -> Vec<Symb> {
+        let mut vars = vec![];
+        let mut todo = vec![pattern.clone()];
+        while let Some(pattern) = todo.pop() {
+            if pattern.is_symbol() {
+                if !self.is_literal(&pattern)
+                    && !self.is_ellipsis(&pattern)
+                    && !pattern.is_symb(Symb::UNDERSCORE)
+                {
+                    vars.push(pattern.to_symb().unwrap());
+                }
+            } else if let ObjVal::Cons(x, y) = &*pattern.val.borrow() {
+                todo.push(x.clone());
+                todo.push(y.clone());
+            }
+        }
+        vars.sort();
+        vars.dedup();
+        vars
+    }
+}
+
+// matching a use against the patterns. a literal matches an identifier
+// that refers to the same binding in the environment of the use as the
+// literal does where the macro was defined, so a local else is not the
+// else of cond.
+struct Matcher<'a> {
+    rules: &'a SyntaxRules,
+    env: &'a Env,
+    use_env: &'a Env,
+}
+
+impl Matcher<'_> {
+    fn match_pattern(
+        &self,
+        pattern: &Obj,
+        input: &Obj,
+        bindings: &mut HashMap<Symb, Binding>,
+    ) -> EvalResult<bool> {
+        if pattern.is_symbol() {
+            if self.rules.is_literal(pattern) {
+                Ok(input.is_symbol()
+                    && self.use_env.binding_of(input)? == self.env.binding_of(pattern)?)
+            } else {
+                if !pattern.is_symb(Symb::UNDERSCORE) {
+                    bindings.insert(pattern.to_symb()?, Binding::One(input.clone()));
+                }
+                Ok(true)
+            }
+        } else if pattern.is_cons() {
+            let (pats, pat_tail) = split_list(pattern)?;
+            let (inputs, input_tail) = split_list(input)?;
+            match pats.iter().position(|p| self.rules.is_ellipsis(p)) {
+                Some(at) if at > 0 => {
+                    self.match_ellipsis(&pats, at, &pat_tail, &inputs, &input_tail, bindings)
+                }
+                _ => {
+                    if inputs.len() < pats.len() {
+                        return Ok(false);
+                    }
+                    for (pat, input) in pats.iter().zip(inputs.iter()) {
+                        if !self.match_pattern(pat, input, bindings)? {
+                            return Ok(false);
+                        }
+                    }
+                    // the tail of the pattern matches the rest of the input.
+                    let rest = inputs[pats.len()..].to_vec();
+                    let rest = Obj::dotted_list_from_vec(rest, input_tail, input.loc.clone());
+                    self.match_pattern(&pat_tail, &rest, bindings)
+                }
+            }
+        } else if pattern.is_null()? {
+            input.is_null()
+        } else {
+            Ok(pattern.is_equal(input))
+        }
+    }
+
+    // (p₁ … pₖ pₑ ⟨ellipsis⟩ pₖ₊₂ … pₙ . tail), pₑ matches as many
+    // elements as the other patterns leave over.
+    fn match_ellipsis(
+        &self,
+        pats: &[Obj],
+        at: usize,
+        pat_tail: &Obj,
+        inputs: &[Obj],
+        input_tail: &Obj,
+        bindings: &mut HashMap<Symb, Binding>,
+    ) -> EvalResult<bool> {
+        let before = &pats[..at - 1];
+        let repeated = &pats[at - 1];
+        let after = &pats[at + 1..];
+        if inputs.len() < before.len() + after.len() {
+            return Ok(false);
+        }
+        if pat_tail.is_null()? && !input_tail.is_null()? {
+            return Ok(false);
+        }
+        let times = inputs.len() - before.len() - after.len();
+        for (pat, input) in before.iter().zip(inputs.iter()) {
+            if !self.match_pattern(pat, input, bindings)? {
+                return Ok(false);
+            }
+        }
+        let mut matches = vec![];
+        for input in inputs[before.len()..before.len() + times].iter() {
+            let mut inner = HashMap::new();
+            if !self.match_pattern(repeated, input, &mut inner)? {
+                return Ok(false);
+            }
+            matches.push(inner);
+        }
+        for var in self.rules.pattern_vars(repeated) {
+            let seq = matches
+                .iter_mut()
+                .map(|inner| inner.remove(&var).unwrap())
+                .collect();
+            bindings.insert(var, Binding::Many(seq));
+        }
+        for (pat, input) in after.iter().zip(inputs[before.len() + times..].iter()) {
+            if !self.match_pattern(pat, input, bindings)? {
+                return Ok(false);
+            }
+        }
+        self.match_pattern(pat_tail, input_tail, bindings)
+    }
+}
+
+// filling in a template.
+struct Expansion<'a> {
+    rules: &'a SyntaxRules,
+    bindings: HashMap<Symb, Binding>,
+    // the alias of every identifier the template put in so far, the
+    // same identifier gets the same alias all through one expansion.
+    renames: HashMap<Symb, Obj>,
+    env: &'a Env,
+    // where the macro was used, the loc of everything the template
+    // builds.
+    loc: Option<Loc>,
+}
+
+impl<'a> Expansion<'a> {
+    // escaped is true inside (⟨ellipsis⟩ ⟨template⟩), where the
+    // ellipsis is an identifier like any other.
+    fn instantiate(&mut self, template: &Obj, escaped: bool) -> EvalResult<Obj> {
+        if template.is_symbol() {
+            match self.bindings.get(&template.to_symb()?) {
+                Some(Binding::One(x)) => Ok(x.clone()),
+                Some(Binding::Many(_)) => {
+                    let msg = "pattern variable used without an ellipsis in template";
+                    Err(LazarusError::eval(msg).with_obj(template))
+                }
+                None => Ok(self.rename(template)),
+            }
+        } else if template.is_cons() {
+            let (items, tail) = split_list(template)?;
+            if !escaped && self.rules.is_ellipsis(&items[0]) && items.len() == 2 {
+                return self.instantiate(&items[1], true);
+            }
+            let mut out = vec![];
+            let mut i = 0;
+            while i < items.len() {
+                let mut depth = 0;
+                while !escaped
+                    && i + depth + 1 < items.len()
+                    && self.rules.is_ellipsis(&items[i + depth + 1])
+                {
+                    depth += 1;
+                }
+                if depth == 0 {
+                    out.push(self.instantiate(&items[i], escaped)?);
+                } else {
+                    out.extend(self.repeat(&items[i], depth)?);
+                }
+                i += depth + 1;
+            }
+            let tail = self.instantiate(&tail, escaped)?;
+            Ok(Obj::dotted_list_from_vec(out, tail, self.loc.clone()))
+        } else {
+            Ok(template.clone())
+        }
+    }
+
+    // a subtemplate followed by depth ellipses, once for every element
+    // of the sequences its pattern variables matched.
+    fn repeat(&mut self, template: &Obj, depth: usize) -> EvalResult<Vec<Obj>> {
+        let vars: Vec<Symb> = self
+            .rules
+            .pattern_vars(template)
+            .into_iter()
+            .filter(|var| match self.bindings.get(var) {
+                Some(Binding::Many(_)) => true,
+                _ => false,
+            })
+            .collect();
+        if vars.is_empty() {
+            let msg = "no pattern variable to repeat in template";
+            return Err(LazarusError::eval(msg).with_obj(template));
+        }
+        let saved: Vec<(Symb, Vec<Binding>)> = vars
+            .iter()
+            .map(|var| match &self.bindings[var] {
+                Binding::Many(seq) => (*var, seq.clone()),
+                Binding::One(_) => unreachable!(),
+            })
+            .collect();
+        let times = saved[0].1.len();
+        if saved.iter().any(|(_, seq)| seq.len() != times) {
+            let msg =
+                "pattern variables under the same ellipsis matched sequences of different lengths";
+            return Err(LazarusError::eval(msg).with_obj(template));
+        }
+        // a failed expansion is thrown away, the bindings only need
+        // restoring when it goes on.
+        let mut out = vec![];
+        for n in 0..times {
+            for (var, seq) in saved.iter() {
+                self.bindings.insert(*var, seq[n].clone());
+            }
+            if depth > 1 {
+                out.extend(self.repeat(template, depth - 1)?);
+            } else {
+                out.push(self.instantiate(template, false)?);
+            }
+        }
+        for (var, seq) in saved {
+            self.bindings.insert(var, Binding::Many(seq));
+        }
+        Ok(out)
+    }
+
+    fn rename(&mut self, id: &Obj) -> Obj {
+        let symb = id.to_symb().unwrap();
+        let (env, loc) = (self.env, self.loc.clone());
+        self.renames
+            .entry(symb)
+            .or_insert_with(|| new_alias(id, env, loc))
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn get_obj(s: &str) -> Obj {
+        let lexer = Lexer::new(s, "test.scm");
+        let mut parser = Parser::new(lexer).unwrap();
+        parser.list().unwrap().to_obj()
+    }
+
+    fn expand_str(spec: &str, form: &str) -> EvalResult<Obj> {
+        let env = Env::the_global_environment();
+        let mac = syntax_rules(&get_obj(spec), &env)?;
+        expand(&mac, &get_obj(form), &env).map(|x| x.strip_syntax())
+    }
+
+    #[test]
+    fn expand_ellipsis_patterns() {
+        let spec =
+            "(syntax-rules () ((_ (a b ...) ... . rest) (quote ((a ...) (b ... ...) rest))))";
+        let result = expand_str(spec, "(m (1 2 3) (4) (5 6) . 7)").unwrap();
+        assert_eq!(result, get_obj("(quote ((1 4 5) (2 3 6) 7))"));
+
+        // patterns after the ellipsis take their elements from the end.
+        let spec = "(syntax-rules () ((_ a ... z) (z a ...)))";
+        let result = expand_str(spec, "(m 1 2 3)").unwrap();
+        assert_eq!(result, get_obj("(3 1 2)"));
+        let result = expand_str(spec, "(m 1)").unwrap();
+        assert_eq!(result, get_obj("(1)"));
+    }
+
+    #[test]
+    fn expand_literals_and_custom_ellipsis() {
+        let spec =
+            "(syntax-rules ::: (to) ((_ a to b :::) (list a (::: :::) (... ...) b :::)) ((_ x) x))";
+        let result = expand_str(spec, "(m 1 to 2 3)").unwrap();
+        assert_eq!(result, get_obj("(list 1 ::: (... ...) 2 3)"));
+        let result = expand_str(spec, "(m 1)").unwrap();
+        assert_eq!(result, Obj::new_int(1, None));
+        match expand_str(spec, "(m 1 from 2)") {
+            Err(LazarusError::Eval { msg, .. }) => assert!(msg.starts_with("no syntax-rules")),
+            result => panic!("expected no rule to match, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn expand_renames_template_identifiers() {
+        let env = Env::the_global_environment();
+        let spec = "(syntax-rules () ((_ x) (let ((tmp x)) (list tmp x))))";
+        let mac = syntax_rules(&get_obj(spec), &env).unwrap();
+        let result = expand(&mac, &get_obj("(m tmp)"), &env).unwrap();
+        let binding = result.cadr().unwrap().car().unwrap();
+        let introduced = binding.car().unwrap();
+        let user = binding.cadr().unwrap();
+        assert!(introduced.is_alias() && !user.is_alias());
+        assert_ne!(introduced.to_symb().unwrap(), user.to_symb().unwrap());
+        assert!(introduced.is_symb(Symb::intern("tmp")));
+        // every occurrence of tmp is the same alias.
+        let body = result.caddr().unwrap();
+        assert_eq!(
+            body.cadr().unwrap().to_symb().unwrap(),
+            introduced.to_symb().unwrap()
+        );
+    }
+}
@@ -5,8 +5,13 @@ impl Token {
         self.tok.pretty()
     }
 
+    /// the source text the token was lexed from.
+    pub fn text(&self) -> String {
+        self.file.source().text[self.start..self.end].to_owned()
+    }
+
     pub fn loc(&self) -> Loc {
-        Loc::new("nofile".to_owned(), self.start, self.end)
+        Loc::new(self.file.clone(), self.start, self.end)
     }
 
     pub fn to_obj(&self) -> Obj {
@@ -15,21 +20,42 @@ impl Token {
 }
 
 impl Tok {
+    /// the special form a reader abbreviation such as 'x stands for.
+    pub fn abbreviation(&self) -> Option<&'static str> {
+        match &self {
+            Tok::Quote => Some("quote"),
+            Tok::Quasiquote => Some("quasiquote"),
+            Tok::Unquote => Some("unquote"),
+            Tok::UnquoteSplicing => Some("unquote-splicing"),
+            _ => None,
+        }
+    }
+
     pub fn pretty(&self) {
         match &self {
-            Tok::Symbol(symb) => print!("{}", symb.name),
+            Tok::Symbol(symb) => print!("{}", symb),
+            Tok::Str(s) => print!("{:?}", s),
+            Tok::Char(c) => print!("#\\{}", char_name(*c)),
             Tok::Float(n) => print!("{}", n),
             Tok::Int(n) => print!("{}", n),
             Tok::LParen => print!("("),
             Tok::RParen => print!(")"),
             Tok::Dot => print!("."),
             Tok::Space => print!(" "),
+            Tok::Comment => print!(" "),
+            Tok::DatumComment => print!("#;"),
+            Tok::Quote => print!("'"),
+            Tok::Quasiquote => print!("`"),
+            Tok::Unquote => print!(","),
+            Tok::UnquoteSplicing => print!(",@"),
         }
     }
 
     pub fn to_objval(&self, loc: Loc) -> Obj {
         match &self {
-            &Tok::Symbol(symb) => Obj::new_symb(symb.name.clone(), Some(loc)),
+            &Tok::Symbol(symb) => Obj::from_symb(*symb, Some(loc)),
+            Tok::Str(s) => Obj::new_str(s.clone(), Some(loc)),
+            Tok::Char(c) => Obj::new_char(*c, Some(loc)),
             Tok::Float(n) => Obj::new_float(*n, Some(loc)),
             Tok::Int(n) => Obj::new_int(*n, Some(loc)),
             _ => panic!(),
@@ -1,48 +1,91 @@
-use std::cell::RefCell;
+pub use crate::error::{CallFrame, LazarusError};
+use crate::source::FileId;
+use gc::{unsafe_empty_trace, Finalize, Gc, GcCell, Trace};
+use gc_derive::{Finalize, Trace};
 use std::collections::HashMap;
 use std::fmt;
-use std::hash::Hash;
-use std::hash::Hasher;
-use std::rc::Rc;
 
-pub fn mutcell<T>(x: T) -> Rc<RefCell<T>> {
-    Rc::new(RefCell::new(x))
+// Objects, environments and frames live on the heap of the gc crate,
+// a tracing collector, so the reference cycles between a closure and
+// the environment it captures are reclaimed once nothing else points
+// at them.
+
+pub fn mutcell<T: Trace>(x: T) -> Gc<GcCell<T>> {
+    Gc::new(GcCell::new(x))
 }
 
-impl Symb {
-    pub fn new(name: &str, filename: String, pos: usize) -> Symb {
-        Symb {
-            name: name.to_owned(),
-            filename,
-            pos,
+/// the names the reader accepts after #\ besides single characters.
+pub const CHAR_NAMES: [(&str, char); 10] = [
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("delete", '\u{7f}'),
+    ("escape", '\u{1b}'),
+    ("newline", '\n'),
+    ("null", '\0'),
+    ("return", '\r'),
+    ("space", ' '),
+    ("tab", '\t'),
+    ("nul", '\0'),
+];
+
+/// how a character is written back out after #\
+pub fn char_name(c: char) -> String {
+    for (name, named) in CHAR_NAMES.iter() {
+        if *named == c {
+            return name.to_string();
         }
     }
-
-    pub fn new_unknown(name: &str) -> Symb {
-        Symb::new(name, "<unknown file>".to_owned(), 0)
+    if c.is_control() {
+        format!("x{:x}", c as u32)
+    } else {
+        c.to_string()
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Loc {
-    pub filename: String,
+    pub file: FileId,
     pub start: usize,
     pub end: usize,
 }
 
 impl Loc {
-    pub fn new(filename: String, start: usize, end: usize) -> Loc {
-        Loc {
-            filename,
-            start,
-            end,
-        }
+    pub fn new(file: FileId, start: usize, end: usize) -> Loc {
+        Loc { file, start, end }
+    }
+
+    /// the span from the start of self to the end of other.
+    pub fn to(&self, other: &Loc) -> Loc {
+        Loc::new(self.file.clone(), self.start, other.end)
+    }
+
+    pub fn filename(&self) -> String {
+        self.file.name().to_owned()
+    }
+
+    /// file:line of the start of the loc, without the column.
+    pub fn file_line(&self) -> String {
+        let (line, _) = self.line_col();
+        format!("{}:{}", self.filename(), line)
+    }
+
+    /// 1-based line and column of the start of the loc.
+    pub fn line_col(&self) -> (usize, usize) {
+        self.file.source().line_col(self.start)
+    }
+}
+
+impl fmt::Display for Loc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, col) = self.line_col();
+        write!(f, "{}:{}:{}", self.filename(), line, col)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub tok: Tok,
+    pub file: FileId,
     pub start: usize,
     pub end: usize,
 }
@@ -50,79 +93,303 @@ pub struct Token {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Tok {
     Symbol(Symb),
+    Str(String),
+    Char(char),
     Float(f64),
     Int(i64),
     LParen,
     RParen,
     Dot,
     Space,
+    Comment,
+    DatumComment,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Rule {
     Exprs,
     List,
+    /// a list whose last node is the tail after the dot: (a b . c)
+    DottedList,
     Empty,
     EmptyList,
 }
 
 #[derive(Debug)]
 pub enum Ast {
-    Node { rule: Rule, nodes: Vec<Ast> },
+    Node {
+        rule: Rule,
+        nodes: Vec<Ast>,
+        loc: Option<Loc>,
+    },
     Leaf(Token),
 }
 
-#[derive(Debug, Clone)]
+/// a symbol, see symbol.rs. where a symbol was written is kept in the
+/// Loc of the token or object that holds it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Symb {
-    pub name: String,
-    pub filename: String,
-    pub pos: usize,
+    /// the index of the name in the symbol table.
+    pub(crate) id: u32,
+    /// 0 for interned symbols, a fresh symbol has a mark of its own.
+    pub(crate) mark: u64,
 }
 
-impl Hash for Symb {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.name.hash(state);
-    }
-}
+impl Finalize for Symb {}
 
-impl PartialEq for Symb {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
-    }
+unsafe impl Trace for Symb {
+    unsafe_empty_trace!();
 }
 
-impl Eq for Symb {}
-
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Trace, Finalize)]
 pub enum ObjVal {
-    Symbol(String),
+    Symbol(Symb),
+    Str(String),
+    Char(char),
     Float(f64),
     Int(i64),
     Bool(bool),
-    Env(Env),
-    PrimFunc(fn(Obj) -> EvalResult<Obj>),
+    Closure(Closure),
+    CaseLambda(CaseLambda),
+    Primitive(Primitive),
+    Alias(Alias),
+    Macro(Macro),
     Cons(Obj, Obj),
     Nil,
 }
 
+/// a procedure made by evaluating a lambda expression. the name is that
+/// of the variable a define bound it to, anonymous lambdas have none.
+#[derive(Clone, Trace, Finalize)]
+pub struct Closure {
+    pub name: Option<Symb>,
+    pub arity: Arity,
+    pub params: Obj,
+    pub body: Obj,
+    pub env: Env,
+    #[unsafe_ignore_trace]
+    pub loc: Option<Loc>,
+}
+
+// closures are compared by identity, comparing the environments they
+// captured would walk into the closures bound there.
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        self.params.is_same(&other.params)
+            && self.body.is_same(&other.body)
+            && std::ptr::eq(&*self.env.frame, &*other.env.frame)
+    }
+}
+
+impl Closure {
+    pub fn new(params: Obj, body: Obj, env: Env, loc: Option<Loc>) -> EvalResult<Closure> {
+        Ok(Closure {
+            name: None,
+            arity: Arity::of_params(&params)?,
+            params,
+            body,
+            env,
+            loc,
+        })
+    }
+}
+
+/// how many arguments a procedure takes. a parameter list ending in a
+/// rest parameter, (a b . rest) or just args, takes at least as many
+/// as the parameters before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub required: usize,
+    pub rest: bool,
+}
+
+unsafe impl Trace for Arity {
+    unsafe_empty_trace!();
+}
+
+impl Finalize for Arity {}
+
+impl Arity {
+    pub fn exactly(required: usize) -> Arity {
+        Arity {
+            required,
+            rest: false,
+        }
+    }
+
+    pub fn at_least(required: usize) -> Arity {
+        Arity {
+            required,
+            rest: true,
+        }
+    }
+
+    pub fn of_params(params: &Obj) -> EvalResult<Arity> {
+        let mut required = 0;
+        let mut params = params.clone();
+        while params.is_cons() {
+            params.car()?.to_symb()?;
+            required += 1;
+            params = params.cdr()?;
+        }
+        if params.is_symbol() {
+            Ok(Arity::at_least(required))
+        } else if params.is_null()? {
+            Ok(Arity::exactly(required))
+        } else {
+            Err(LazarusError::wrong_type("parameter list", &params))
+        }
+    }
+
+    pub fn accepts(&self, n: usize) -> bool {
+        n == self.required || (self.rest && n > self.required)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rest {
+            write!(f, "at least {}", self.required)
+        } else {
+            write!(f, "{}", self.required)
+        }
+    }
+}
+
+/// a procedure made by case-lambda. applying it applies the first
+/// clause that accepts that many arguments.
+#[derive(Clone, PartialEq, Trace, Finalize)]
+pub struct CaseLambda {
+    pub name: Option<Symb>,
+    pub clauses: Vec<Closure>,
+    #[unsafe_ignore_trace]
+    pub loc: Option<Loc>,
+}
+
+/// a procedure implemented in rust, it takes its arguments as a list.
+/// apply checks their number against arity before calling func.
+#[derive(Clone, Trace, Finalize)]
+pub struct Primitive {
+    pub name: Symb,
+    pub arity: Arity,
+    pub func: fn(Obj) -> EvalResult<Obj>,
+}
+
+impl PartialEq for Primitive {
+    fn eq(&self, other: &Self) -> bool {
+        self.func as usize == other.func as usize
+    }
+}
+
 impl fmt::Debug for ObjVal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ObjVal::Symbol(s) => write!(f, "'{}", s),
+            ObjVal::Str(s) => write!(f, "{:?}", s),
+            ObjVal::Char(c) => write!(f, "#\\{}", char_name(*c)),
             ObjVal::Float(n) => write!(f, "{}", n),
             ObjVal::Int(n) => write!(f, "{}", n),
             ObjVal::Bool(b) => write!(f, "{:?}", b),
-            ObjVal::Env(_) => write!(f, "<env>"),
-            ObjVal::PrimFunc(_) => write!(f, "<fn>"),
+            ObjVal::Closure(c) => write_procedure(f, c.name, &c.loc),
+            ObjVal::CaseLambda(c) => write_procedure(f, c.name, &c.loc),
+            ObjVal::Primitive(p) => write!(f, "#<procedure {}>", p.name),
+            ObjVal::Alias(a) => write!(f, "{:?}", a.base),
+            ObjVal::Macro(m) => match m.name {
+                Some(name) => write!(f, "#<macro {}>", name.name()),
+                None => write!(f, "#<macro>"),
+            },
             ObjVal::Cons(x, y) => write!(f, "({:?} . {:?})", x, y),
             ObjVal::Nil => write!(f, "()"),
         }
     }
 }
 
-#[derive(Clone)]
+/// an identifier a macro put into its expansion. symb is a fresh
+/// symbol, so a binding the expansion makes with it can't capture a
+/// variable of the code around the macro use. a free alias means what
+/// base means in env, the environment the macro was defined in.
+#[derive(Clone, Trace, Finalize)]
+pub struct Alias {
+    pub symb: Symb,
+    pub base: Obj,
+    pub env: Env,
+}
+
+impl PartialEq for Alias {
+    fn eq(&self, other: &Self) -> bool {
+        self.symb == other.symb
+    }
+}
+
+/// a macro, what define-syntax, let-syntax, letrec-syntax and
+/// define-macro bind a keyword to. see syntax.rs.
+#[derive(Clone, Trace, Finalize)]
+pub struct Macro {
+    pub name: Option<Symb>,
+    pub transformer: Transformer,
+    /// where the macro was defined.
+    pub env: Env,
+}
+
+// like closures, macros are compared by identity.
+impl PartialEq for Macro {
+    fn eq(&self, other: &Self) -> bool {
+        self.transformer == other.transformer && std::ptr::eq(&*self.env.frame, &*other.env.frame)
+    }
+}
+
+#[derive(Clone, PartialEq, Trace, Finalize)]
+pub enum Transformer {
+    SyntaxRules(SyntaxRules),
+    /// define-macro, a procedure from the form of the use, or only its
+    /// operands, to the expansion.
+    Procedure {
+        procedure: Obj,
+        operands: bool,
+    },
+    /// er-macro-transformer, a procedure of the form, rename and
+    /// compare.
+    ExplicitRenaming(Obj),
+}
+
+/// (syntax-rules ⟨ellipsis⟩ (⟨literal⟩ …) (⟨pattern⟩ ⟨template⟩) …)
+#[derive(Clone, PartialEq, Trace, Finalize)]
+pub struct SyntaxRules {
+    pub ellipsis: Symb,
+    pub literals: Vec<Symb>,
+    pub rules: Vec<SyntaxRule>,
+}
+
+#[derive(Clone, PartialEq, Trace, Finalize)]
+pub struct SyntaxRule {
+    pub pattern: Obj,
+    pub template: Obj,
+}
+
+// #<procedure fact fact.scm:2>
+fn write_procedure(
+    f: &mut fmt::Formatter<'_>,
+    name: Option<Symb>,
+    loc: &Option<Loc>,
+) -> fmt::Result {
+    write!(f, "#<procedure")?;
+    if let Some(name) = name {
+        write!(f, " {}", name.name())?;
+    }
+    if let Some(loc) = loc {
+        write!(f, " {}", loc.file_line())?;
+    }
+    write!(f, ">")
+}
+
+#[derive(Clone, Trace, Finalize)]
 pub struct Obj {
-    pub val: Rc<RefCell<ObjVal>>,
+    pub val: Gc<GcCell<ObjVal>>,
+    #[unsafe_ignore_trace]
     pub loc: Option<Loc>, // experimental
 }
 
@@ -134,27 +401,41 @@ impl fmt::Debug for Obj {
 
 impl PartialEq for Obj {
     fn eq(&self, other: &Self) -> bool {
-        self.val == other.val
+        self.is_equal(other)
     }
 }
 
 impl Eq for Obj {}
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Trace, Finalize)]
 pub struct Env {
     pub id: usize,
-    pub frame: Rc<RefCell<Frame>>,
+    pub frame: Gc<GcCell<Frame>>,
     /// if enclosing is None, then it is the global environment.
     pub enclosing: Option<Box<Env>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Trace)]
 pub struct Frame {
     pub symbol_table: HashMap<Symb, Obj>,
 }
 
-pub type EvalResult<T> = Result<T, String>;
+// the collector finalizes a frame right before it frees it, tests count
+// the frames freed on their thread.
+#[cfg(test)]
+thread_local! {
+    pub(crate) static FRAMES_FREED: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+impl Finalize for Frame {
+    #[cfg(test)]
+    fn finalize(&self) {
+        FRAMES_FREED.with(|n| n.set(n.get() + 1));
+    }
+}
+
+pub type EvalResult<T> = Result<T, LazarusError>;
 
 pub fn unimplemented_eval<T>() -> EvalResult<T> {
-    Err("unimplemented".to_string())
+    Err(LazarusError::eval("unimplemented"))
 }
@@ -1,7 +1,10 @@
+;; mkCycle builds an env <-> closure reference cycle on every call,
+;; get-x captures the frame it is defined in.
+;; running monster3 creates 50 * 100 * 100 of them, see notes.org.
 (begin
   (define mkCycle (lambda ()
                     (define x 1)
-                    (set! x (list x))
+                    (define get-x (lambda () x))
                     (quote ok)))
   (define dec (lambda (n) (- n 1)))
   
@@ -1,3 +1,4 @@
+;; iterative factorial, fact-iter calls itself in tail position.
 (begin
 
   (define factorial (lambda (n) 
@@ -1,3 +1,4 @@
+;; recursive factorial.
 (begin
   (define fact (lambda (n)
                  (if (eq? n 0) 1
@@ -1,2 +1,3 @@
+;; smallest possible program.
 (begin (* 3 4))
 
@@ -1,3 +1,4 @@
+;; message passing: point returns a dispatch procedure closed over x and y.
 (begin
   (define point
     (lambda (x y)      
@@ -0,0 +1,4 @@
+;; top level forms are evaluated in order, no (begin ...) needed.
+(define square (lambda (x) (* x x)))
+(define sum-squares (lambda (a b) (+ (square a) (square b))))
+(sum-squares 3 4)
@@ -1,3 +1,4 @@
+;; unicode symbols name procedures just like ascii ones.
 (begin
   (define map (lambda (f xs)
                 (if (eq? #t (null? xs)) (list)